@@ -8,9 +8,11 @@
 //! - Pagination and query search structs.
 //! - Basic types for managing DB connections and transactions (`sqlx-postgres` feature).
 //! - Basic methods to easily deals with streams and integration tests.
+//! - Middlewares, like one to catch the panics of the handlers.
 //!
 //! > (❗️) This project is in a very early stage.
 
+pub mod middleware;
 pub mod page;
 pub mod query;
 pub mod response;
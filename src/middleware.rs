@@ -0,0 +1,152 @@
+//! Middlewares for Actix apps.
+
+use crate::result::AppError;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_core::future::LocalBoxFuture;
+use std::any::Any;
+use std::future::{poll_fn, ready, Future, Ready};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::task::Poll;
+
+/// Middleware that catches the panics of the endpoint handlers, returning an
+/// `HTTP 500 Internal Server Error` response with the same JSON payload
+/// of [`AppError::Unexpected`] errors, instead of an empty response.
+///
+/// As any other internal error, the panic is logged and reported
+/// with the [`ErrorReporter`](crate::result::ErrorReporter) set, if any.
+///
+/// # Example
+/// ```
+/// use actix_web::App;
+/// use actix_contrib_rest::middleware::CatchPanic;
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(CatchPanic);
+///         // .service(...)
+/// }
+/// ```
+///
+/// Panics when the request is dispatched or while the handler runs get
+/// the same response, and are reported:
+/// ```
+/// use actix_contrib_rest::middleware::CatchPanic;
+/// use actix_contrib_rest::result::{set_error_reporter, AppError, ErrorContext, InternalErrorPayload};
+/// use actix_web::dev::Service;
+/// use actix_web::http::StatusCode;
+/// use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+/// use actix_web::{web, App, HttpResponse};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static REPORTED: AtomicUsize = AtomicUsize::new(0);
+///
+/// async fn boom() -> HttpResponse {
+///     panic!("boom")
+/// }
+///
+/// #[actix_web::main]
+/// async fn main() {
+///     set_error_reporter(|error: &AppError, _: &ErrorContext| {
+///         assert!(error.to_string().starts_with("Handler panicked:"));
+///         REPORTED.fetch_add(1, Ordering::SeqCst);
+///     });
+///     let app = init_service(
+///         App::new()
+///             .wrap_fn(|req, srv| {
+///                 if req.path() == "/dispatch" {
+///                     panic!("dispatch");
+///                 }
+///                 srv.call(req)
+///             })
+///             .wrap(CatchPanic)
+///             .route("/boom", web::get().to(boom)),
+///     )
+///     .await;
+///
+///     for uri in ["/boom", "/dispatch"] {
+///         let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+///         assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+///         let payload: InternalErrorPayload = read_body_json(res).await;
+///         assert_eq!(payload.error, "Internal Server Error");
+///         assert_eq!(payload.retryable, Some(false));
+///     }
+///     assert_eq!(REPORTED.load(Ordering::SeqCst), 2);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CatchPanicMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware { service }))
+    }
+}
+
+/// Service created by the [`CatchPanic`] middleware.
+pub struct CatchPanicMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let http_req = req.request().clone();
+        let fut = catch_unwind(AssertUnwindSafe(|| self.service.call(req)));
+        Box::pin(async move {
+            let result = match fut {
+                Ok(fut) => {
+                    let mut fut = Box::pin(fut);
+                    poll_fn(|cx| match catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(cx))) {
+                        Ok(Poll::Pending) => Poll::Pending,
+                        Ok(Poll::Ready(res)) => Poll::Ready(Ok(res)),
+                        Err(panic) => Poll::Ready(Err(panic)),
+                    })
+                    .await
+                }
+                Err(panic) => Err(panic),
+            };
+            match result {
+                Ok(res) => res.map(ServiceResponse::map_into_left_body),
+                Err(panic) => {
+                    let message = panic_message(&*panic);
+                    let err = AppError::Unexpected(anyhow::anyhow!("Handler panicked: {}", message));
+                    let res = err.response_for(&http_req);
+                    Ok(ServiceResponse::new(http_req, res).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+/// The message passed to `panic!`, if any.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
@@ -1,23 +1,62 @@
 //! Map page responses.
+//!
+//! The settings of the module, [`set_page_format()`] and [`set_empty_page_hint()`],
+//! are global to the process, and are meant to be set at startup.
 
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "sqlx-postgres")]
+use crate::db::Tx;
+use crate::query::{deserialize_non_negative, deserialize_non_negative_opt, stable_hash, QuerySearch};
+use crate::result::{read_setting, write_setting, AppError, ErrorCode, Result, ValidationErrorPayload};
+
+use actix_http::error::PayloadError;
+use actix_web::body::BoxBody;
+#[cfg(feature = "csv")]
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::header::{EntityTag, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder, ResponseError};
+use awc::ClientResponse;
+use futures_core::stream::Stream;
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::postgres::{PgArguments, PgRow};
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::query::{QueryAs, QueryScalar};
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::{FromRow, Postgres};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::RwLock;
+use std::task::{Context, Poll};
 
 /// Struct used to serialize and deserialize paginated results.
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Page<T> {
+/// Negative `offset`, `page_size` and `total` values are
+/// rejected when deserialized.
+///
+/// The envelope of the serialized pages can be customized
+/// with [`set_page_format()`].
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Page<T, M = ()> {
     /// The data in the page, an empty `[]` vector
     /// if there is no results.
     pub data: Vec<T>,
     /// the offset from the full results, normally
     /// zero indexed.
+    #[serde(deserialize_with = "deserialize_non_negative")]
     pub offset: i64,
     /// The size of the current page result, that could
     /// be <= to the size requested depending on how many
     /// results you get.
+    #[serde(deserialize_with = "deserialize_non_negative")]
     pub page_size: i64,
     /// The total results count including the ones included
     /// in this page.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_non_negative_opt")]
     pub total: Option<i64>,
 
     /// A message that might be presented to the user along
@@ -30,6 +69,188 @@ pub struct Page<T> {
     /// that is deprecated.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub warning: Option<String>,
+    /// Navigation links to other pages, see [`Page::with_links()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<PageLinks>,
+
+    /// The number of pages, only if the total is known,
+    /// see [`Page::with_page_info()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<i64>,
+    /// The number of the current page, starting from 1,
+    /// see [`Page::with_page_info()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_page: Option<i64>,
+    /// Whether there are more results after this page,
+    /// see [`Page::with_page_info()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_next: Option<bool>,
+
+    /// Counts of the results per value of some fields, e.g.
+    /// the number of products per category in a search.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<HashMap<String, Vec<FacetCount>>>,
+
+    /// Metadata of any type attached to the page, e.g. stats of the
+    /// query execution or the filters applied, see [`Page::with_meta()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<M>,
+}
+
+/// The count of results with a value of a field, see [`Page::facets`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Format of the envelope of the serialized [`Page`] results,
+/// see [`set_page_format()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFormat {
+    /// The name of the key with the data, by default `data`.
+    pub data_key: &'static str,
+    /// The name of the object where the rest of the fields (`offset`,
+    /// `page_size`, `total`...) are nested, by default `None`, so
+    /// the fields are serialized alongside the data.
+    pub meta_key: Option<&'static str>,
+}
+
+impl Default for PageFormat {
+    fn default() -> Self {
+        DEFAULT_PAGE_FORMAT
+    }
+}
+
+const DEFAULT_PAGE_FORMAT: PageFormat = PageFormat { data_key: "data", meta_key: None };
+
+static PAGE_FORMAT: RwLock<PageFormat> = RwLock::new(DEFAULT_PAGE_FORMAT);
+
+/// Set the format of the envelope of the serialized [`Page`] results,
+/// e.g. to match existing API contracts. Only the serialization
+/// is affected, the pages are always deserialized with the
+/// default format.
+///
+/// ```
+/// use actix_contrib_rest::page::{set_page_format, Page, PageFormat};
+/// use serde_json::json;
+///
+/// set_page_format(PageFormat { data_key: "items", meta_key: Some("meta") });
+/// let page = Page::with_data(vec![1, 2], Some(2), 0);
+/// assert_eq!(
+///     serde_json::to_value(page).unwrap(),
+///     json!({ "items": [1, 2], "meta": { "offset": 0, "page_size": 2, "total": 2 } })
+/// );
+/// ```
+pub fn set_page_format(format: PageFormat) {
+    *write_setting(&PAGE_FORMAT) = format;
+}
+
+type EmptyPageHint = dyn Fn(&QuerySearch) -> Option<String> + Send + Sync;
+
+static EMPTY_PAGE_HINT: RwLock<Option<Box<EmptyPageHint>>> = RwLock::new(None);
+
+/// Set the hook called with the query of the request when a search
+/// (non-empty `q` argument) doesn't match any result, returning the
+/// message presented to the user along the empty page, e.g. suggestions
+/// like "try removing filters". It's applied by [`Page::with_hint()`].
+///
+/// ```
+/// use actix_contrib_rest::page::{set_empty_page_hint, Page};
+/// use actix_contrib_rest::query::QuerySearch;
+///
+/// set_empty_page_hint(|query| {
+///     Some(format!("No results for \"{}\", try removing filters", query.q.as_deref()?))
+/// });
+/// let query = QuerySearch { q: Some("jhon".into()), page_size: 10, ..Default::default() };
+/// let page = Page::<String>::empty().with_hint(&query);
+/// assert_eq!(page.message.as_deref(), Some("No results for \"jhon\", try removing filters"));
+/// ```
+pub fn set_empty_page_hint(hook: impl Fn(&QuerySearch) -> Option<String> + Send + Sync + 'static) {
+    *write_setting(&EMPTY_PAGE_HINT) = Some(Box::new(hook));
+}
+
+/// The fields of a [`Page`] other than the data.
+struct PageMeta<'a, T, M>(&'a Page<T, M>);
+
+impl<T, M: Serialize> PageMeta<'_, T, M> {
+    /// Serialize the fields into the map, skipping the ones not set.
+    fn serialize_entries<S: SerializeMap>(&self, map: &mut S) -> core::result::Result<(), S::Error> {
+        let page = self.0;
+        map.serialize_entry("offset", &page.offset)?;
+        map.serialize_entry("page_size", &page.page_size)?;
+        if let Some(total) = page.total {
+            map.serialize_entry("total", &total)?;
+        }
+        if let Some(message) = &page.message {
+            map.serialize_entry("message", message)?;
+        }
+        if let Some(warning) = &page.warning {
+            map.serialize_entry("warning", warning)?;
+        }
+        if let Some(links) = &page.links {
+            map.serialize_entry("links", links)?;
+        }
+        if let Some(total_pages) = page.total_pages {
+            map.serialize_entry("total_pages", &total_pages)?;
+        }
+        if let Some(current_page) = page.current_page {
+            map.serialize_entry("current_page", &current_page)?;
+        }
+        if let Some(has_next) = page.has_next {
+            map.serialize_entry("has_next", &has_next)?;
+        }
+        if let Some(facets) = &page.facets {
+            map.serialize_entry("facets", facets)?;
+        }
+        if let Some(meta) = &page.meta {
+            map.serialize_entry("meta", meta)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, M: Serialize> Serialize for PageMeta<'_, T, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        self.serialize_entries(&mut map)?;
+        map.end()
+    }
+}
+
+impl<T: Serialize, M: Serialize> Serialize for Page<T, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let format = *read_setting(&PAGE_FORMAT);
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(format.data_key, &self.data)?;
+        match format.meta_key {
+            Some(meta_key) => map.serialize_entry(meta_key, &PageMeta(self))?,
+            None => PageMeta(self).serialize_entries(&mut map)?,
+        }
+        map.end()
+    }
+}
+
+/// Navigation links of a [`Page`], with the URLs (path and query string)
+/// to request the pages.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PageLinks {
+    /// The current page.
+    #[serde(rename = "self")]
+    pub current: String,
+    /// The first page.
+    pub first: String,
+    /// The previous page, if the current page is not the first one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    /// The next page, if there are more results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    /// The last page, only if the total is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
 }
 
 impl<T> From<Vec<T>> for Page<T> {
@@ -44,6 +265,12 @@ impl<T> From<Vec<T>> for Page<T> {
             total: Some(len),
             message: None,
             warning: None,
+            links: None,
+            total_pages: None,
+            current_page: None,
+            has_next: None,
+            facets: None,
+            meta: None,
         }
     }
 }
@@ -58,6 +285,12 @@ impl<T> Page<T> {
             total: Some(0),
             message: None,
             warning: None,
+            links: None,
+            total_pages: None,
+            current_page: None,
+            has_next: None,
+            facets: None,
+            meta: None,
         }
     }
 
@@ -71,6 +304,1071 @@ impl<T> Page<T> {
             page_size,
             message: None,
             warning: None,
+            links: None,
+            total_pages: None,
+            current_page: None,
+            has_next: None,
+            facets: None,
+            meta: None,
+        }
+    }
+
+    /// Create page with the rows of a query that fetched one row more than
+    /// the page size requested (`LIMIT page_size + 1`), to know whether
+    /// there are more results without an expensive `COUNT` query: the
+    /// extra row is dropped, and `has_next` is set to whether it was fetched.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::from_lookahead(vec![1, 2, 3], 2, 0);
+    /// assert_eq!(page.data, &[1, 2]);
+    /// assert_eq!(page.has_next, Some(true));
+    /// assert_eq!(Page::from_lookahead(vec![3], 2, 2).has_next, Some(false));
+    /// ```
+    pub fn from_lookahead(mut rows: Vec<T>, page_size: i64, offset: i64) -> Self {
+        let has_next = rows.len() as i64 > page_size;
+        rows.truncate(page_size.max(0) as usize);
+        Page {
+            has_next: Some(has_next),
+            ..Page::with_data(rows, None, offset)
+        }
+    }
+
+    /// Create page with the data, total, offset and page size passed,
+    /// checking that the pagination metadata is consistent: the offset is
+    /// not negative, and the page size and the total (if known) are not
+    /// lower than the size of the data. Otherwise [`AppError::Validation`]
+    /// is returned with the `"invalid_page"` code.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// assert!(Page::try_new(vec![1, 2], Some(10), 0, 5).is_ok());
+    /// assert!(Page::try_new(vec![1, 2], Some(1), 0, 5).is_err());
+    /// assert!(Page::try_new(vec![1, 2], None, -1, 5).is_err());
+    /// assert!(Page::try_new(vec![1, 2], None, 0, 1).is_err());
+    /// ```
+    pub fn try_new(data: Vec<T>, total: Option<i64>, offset: i64, page_size: i64) -> Result<Self> {
+        let mut page = Page::with_data(data, total, offset);
+        page.page_size = page_size;
+        match page.inconsistency() {
+            Some(error) => Err(AppError::Validation(Some(ErrorCode::InvalidPage.as_str()), error)),
+            None => Ok(page),
+        }
+    }
+}
+
+impl<T: DeserializeOwned, M: DeserializeOwned> Page<T, M> {
+    /// Deserialize the page from the JSON body passed, e.g. of a response of
+    /// other services built with this crate, checking that the pagination
+    /// metadata is consistent (see [`Page::try_new()`]). If the body cannot
+    /// be deserialized or the page is inconsistent, [`AppError::Upstream`]
+    /// is returned with the name of the service passed.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::<String>::from_slice(br#"{"data": ["a", "b"], "offset": 0, "page_size": 2, "total": 8}"#, "users").unwrap();
+    /// assert_eq!(page.total, Some(8));
+    /// assert!(Page::<String>::from_slice(br#"{"data": ["a", "b"], "offset": 0, "page_size": 2, "total": 1}"#, "users").is_err());
+    /// ```
+    pub fn from_slice(body: &[u8], service: &'static str) -> Result<Self> {
+        Self::from_body(body, service, None)
+    }
+
+    /// Deserialize the page from the body of the response of the service
+    /// passed, like [`Page::from_slice()`]. If the response status is not
+    /// successful, [`AppError::Upstream`] is returned with the status.
+    pub async fn from_response<S>(res: &mut ClientResponse<S>, service: &'static str) -> Result<Self>
+    where
+        S: Stream<Item = core::result::Result<Bytes, PayloadError>> + Unpin,
+    {
+        let status = res.status();
+        if !status.is_success() {
+            return Err(upstream_error(service, Some(status), anyhow::anyhow!("Unexpected status {status}")));
+        }
+        let body = res.body().await.map_err(|e| upstream_error(service, Some(status), e.into()))?;
+        Self::from_body(&body, service, Some(status))
+    }
+
+    fn from_body(body: &[u8], service: &'static str, status: Option<StatusCode>) -> Result<Self> {
+        let page: Self = serde_json::from_slice(body).map_err(|e| upstream_error(service, status, e.into()))?;
+        match page.inconsistency() {
+            Some(error) => Err(upstream_error(service, status, anyhow::Error::msg(error))),
+            None => Ok(page),
+        }
+    }
+}
+
+/// Error of a paginated response of another service.
+fn upstream_error(service: &'static str, status: Option<StatusCode>, source: anyhow::Error) -> AppError {
+    AppError::Upstream { service, status, source }
+}
+
+impl<T, M> Page<T, M> {
+    /// The reason why the pagination metadata is inconsistent, if it is.
+    fn inconsistency(&self) -> Option<String> {
+        let len = self.data.len() as i64;
+        if self.offset < 0 {
+            Some(format!("The offset {} cannot be negative", self.offset))
+        } else if self.page_size < len {
+            Some(format!("The page size {} is lower than the size of the data {len}", self.page_size))
+        } else {
+            self.total
+                .filter(|total| *total < len)
+                .map(|total| format!("The total {total} is lower than the size of the data {len}"))
+        }
+    }
+
+    /// Set the message presented to the user along the results.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::from(vec![1, 2])
+    ///     .with_total(120)
+    ///     .with_message("Refine the search to get better results")
+    ///     .with_warning("The v1 endpoint is deprecated");
+    /// assert_eq!(page.total, Some(120));
+    /// assert_eq!(page.message.as_deref(), Some("Refine the search to get better results"));
+    /// assert_eq!(page.warning.as_deref(), Some("The v1 endpoint is deprecated"));
+    /// ```
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Set the message returned by the hook set with [`set_empty_page_hint()`],
+    /// if any, when the page has no results for the search of the query
+    /// passed (non-empty `q` argument). The message already set is kept.
+    pub fn with_hint(mut self, query: &QuerySearch) -> Self {
+        let searching = query.q.as_deref().is_some_and(|q| !q.trim().is_empty());
+        if self.data.is_empty() && searching && self.message.is_none() {
+            if let Some(hook) = read_setting(&EMPTY_PAGE_HINT).as_deref() {
+                self.message = hook(query);
+            }
+        }
+        self
+    }
+
+    /// Set the warning message presented to the user along the results.
+    pub fn with_warning(mut self, warning: impl Into<String>) -> Self {
+        self.warning = Some(warning.into());
+        self
+    }
+
+    /// Set the total results count.
+    pub fn with_total(mut self, total: i64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Set the navigation links of the page (`self`, `first`, `prev`, `next`
+    /// and `last`), built with the path of the request and the query
+    /// string, replacing the `offset` and `page_size` arguments. If the
+    /// total is unknown, the `last` link is not set, and the `next` link is
+    /// set if the page is full, unless `has_next` was already set, e.g. by
+    /// [`Page::from_lookahead()`], the same as [`Page::with_page_info()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// use actix_web::test::TestRequest;
+    ///
+    /// let req = TestRequest::get().uri("/users?q=john&offset=10&page_size=10").to_http_request();
+    /// let query = QuerySearch { q: Some("john".to_owned()), offset: 10, page_size: 10, ..Default::default() };
+    /// let page = Page::with_data(vec![1; 10], Some(25), 10).with_links(&req, &query);
+    /// let links = page.links.unwrap();
+    /// assert_eq!(links.current, "/users?q=john&offset=10&page_size=10");
+    /// assert_eq!(links.first, "/users?q=john&offset=0&page_size=10");
+    /// assert_eq!(links.prev.as_deref(), Some("/users?q=john&offset=0&page_size=10"));
+    /// assert_eq!(links.next.as_deref(), Some("/users?q=john&offset=20&page_size=10"));
+    /// assert_eq!(links.last.as_deref(), Some("/users?q=john&offset=20&page_size=10"));
+    /// ```
+    pub fn with_links(mut self, req: &HttpRequest, query: &QuerySearch) -> Self {
+        self.links = Some(PageLinks::build(
+            req,
+            query.offset,
+            query.page_size,
+            self.total,
+            self.data.len(),
+            self.has_next,
+        ));
+        self
+    }
+
+    /// Set the `total_pages`, `current_page` and `has_next` fields, computed
+    /// with the offset, the total and the size of the pages requested, so
+    /// clients don't need to compute them. If the total is unknown,
+    /// `total_pages` is not set, and `has_next` is true if the page is full,
+    /// unless it was already set, e.g. by [`Page::from_lookahead()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::with_data(vec![1; 10], Some(25), 10).with_page_info(10);
+    /// assert_eq!(page.total_pages, Some(3));
+    /// assert_eq!(page.current_page, Some(2));
+    /// assert_eq!(page.has_next, Some(true));
+    /// ```
+    pub fn with_page_info(mut self, page_size: i64) -> Self {
+        let page_size = page_size.max(1);
+        let len = self.data.len() as i64;
+        self.total_pages = self.total.map(|total| (total + page_size - 1) / page_size);
+        self.current_page = Some(self.offset.max(0) / page_size + 1);
+        let has_next = has_next_page(self.offset, len, page_size, self.total, self.has_next);
+        self.has_next = Some(has_next);
+        self
+    }
+
+    /// Set the counts of the results per value of the field passed.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::{FacetCount, Page};
+    ///
+    /// let page = Page::with_data(vec!["Shirt", "Hat"], Some(2), 0)
+    ///     .with_facets("category", vec![FacetCount { value: "clothes".to_owned(), count: 2 }]);
+    /// assert_eq!(page.facets.unwrap()["category"][0].count, 2);
+    /// ```
+    pub fn with_facets(mut self, field: impl Into<String>, counts: Vec<FacetCount>) -> Self {
+        self.facets.get_or_insert_with(HashMap::new).insert(field.into(), counts);
+        self
+    }
+
+    /// Merge the page passed into this one, e.g. with the results fetched
+    /// from several shards or sources: the data is appended, the totals
+    /// and the facets counts are added (the total is unknown if any of them
+    /// is unknown), and the offset is the lowest one. The links and the
+    /// page info are cleared, because they are not valid anymore.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let mut page = Page::with_data(vec![1, 2], Some(10), 0);
+    /// page.extend(Page::with_data(vec![3], Some(5), 0));
+    /// assert_eq!(page.data, vec![1, 2, 3]);
+    /// assert_eq!(page.page_size, 3);
+    /// assert_eq!(page.total, Some(15));
+    /// ```
+    pub fn extend(&mut self, other: Page<T, M>) {
+        self.data.extend(other.data);
+        self.offset = self.offset.min(other.offset);
+        self.page_size = self.data.len() as i64;
+        self.total = self.total.zip(other.total).map(|(a, b)| a + b);
+        self.message = self.message.take().or(other.message);
+        self.warning = self.warning.take().or(other.warning);
+        self.meta = self.meta.take().or(other.meta);
+        self.links = None;
+        self.total_pages = None;
+        self.current_page = None;
+        self.has_next = None;
+        for (field, counts) in other.facets.into_iter().flatten() {
+            let merged = self.facets.get_or_insert_with(HashMap::new).entry(field).or_default();
+            for count in counts {
+                match merged.iter_mut().find(|c| c.value == count.value) {
+                    Some(c) => c.count += count.count,
+                    None => merged.push(count),
+                }
+            }
+        }
+    }
+
+    /// Split the page into pages of `size` items (at least 1), e.g. to page
+    /// an oversized result in memory. The offsets of the pages are computed
+    /// from the offset of this page, and the rest of the fields are
+    /// kept, except the links and the page info.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let pages = Page::with_data(vec![1, 2, 3, 4, 5], Some(5), 0).chunks(2);
+    /// assert_eq!(pages.len(), 3);
+    /// assert_eq!(pages[1].data, vec![3, 4]);
+    /// assert_eq!(pages[1].offset, 2);
+    /// assert_eq!(pages[2].page_size, 1);
+    /// ```
+    pub fn chunks(self, size: usize) -> Vec<Page<T, M>>
+    where
+        M: Clone,
+    {
+        let size = size.max(1);
+        let mut pages = Vec::new();
+        let mut data = self.data.into_iter().peekable();
+        let mut offset = self.offset;
+        while data.peek().is_some() {
+            let mut page = Page::with_data(data.by_ref().take(size).collect(), self.total, offset);
+            page.message = self.message.clone();
+            page.warning = self.warning.clone();
+            page.facets = self.facets.clone();
+            offset += page.page_size;
+            let data = std::mem::take(&mut page.data);
+            pages.push(page.with_parts(data, self.meta.clone()));
+        }
+        pages
+    }
+
+    /// Convert the items of the page with the function passed, e.g. to map
+    /// the DB rows into response DTOs, keeping the rest of the fields.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::with_data(vec![1, 2], Some(12), 10).map(|n| n.to_string());
+    /// assert_eq!(page.data, vec!["1", "2"]);
+    /// assert_eq!(page.total, Some(12));
+    /// assert_eq!(page.offset, 10);
+    /// ```
+    pub fn map<U>(mut self, f: impl FnMut(T) -> U) -> Page<U, M> {
+        let data = std::mem::take(&mut self.data).into_iter().map(f).collect();
+        let meta = self.meta.take();
+        self.with_parts(data, meta)
+    }
+
+    /// Convert the items of the page with the function passed, like
+    /// [`Page::map()`], but failing with the first error returned.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    ///
+    /// let page = Page::from(vec!["1", "2"]).try_map(|s| s.parse::<i32>()).unwrap();
+    /// assert_eq!(page.data, vec![1, 2]);
+    /// assert!(Page::from(vec!["1", "x"]).try_map(|s| s.parse::<i32>()).is_err());
+    /// ```
+    pub fn try_map<U, E>(
+        mut self,
+        f: impl FnMut(T) -> core::result::Result<U, E>,
+    ) -> core::result::Result<Page<U, M>, E> {
+        let data = std::mem::take(&mut self.data)
+            .into_iter()
+            .map(f)
+            .collect::<core::result::Result<_, _>>()?;
+        let meta = self.meta.take();
+        Ok(self.with_parts(data, meta))
+    }
+
+    /// Convert the items of the page into JSON objects with only the fields
+    /// passed (sparse fieldsets), e.g. the ones requested with the `fields`
+    /// argument (see [`Fields`](crate::query::Fields)), to reduce the size of
+    /// the payloads. If no fields are passed, the items are kept whole.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use actix_contrib_rest::query::Fields;
+    /// use serde_json::json;
+    ///
+    /// let fields = Fields { fields: Some("id,name".to_owned()) };
+    /// let page = Page::from(vec![json!({ "id": 1, "name": "John", "email": "john@example.com" })])
+    ///     .project(&fields.parse());
+    /// assert_eq!(page.data, vec![json!({ "id": 1, "name": "John" })]);
+    /// ```
+    pub fn project(self, fields: &[impl AsRef<str>]) -> Page<Value, M>
+    where
+        T: Serialize,
+    {
+        self.map(|item| {
+            let mut value = serde_json::to_value(item).unwrap_or_default();
+            if let (Value::Object(map), false) = (&mut value, fields.is_empty()) {
+                map.retain(|key, _| fields.iter().any(|f| f.as_ref() == key));
+            }
+            value
+        })
+    }
+
+    /// Convert the items of the page into JSON objects with the terms of the
+    /// search highlighted in the text fields passed, wrapped with the `pre`
+    /// and `post` markers (see [`QuerySearch::highlight_text()`]), if the
+    /// `highlight` argument of the query is true, so search UIs can show
+    /// the snippets matched without re-matching the terms client-side.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// use serde_json::json;
+    ///
+    /// let query = QuerySearch { q: Some("john".into()), highlight: Some(true), ..Default::default() };
+    /// let page = Page::from(vec![json!({ "name": "John Doe", "email": "john@example.com" })])
+    ///     .highlight(&query, &["name"], "<em>", "</em>");
+    /// assert_eq!(page.data, vec![json!({ "name": "<em>John</em> Doe", "email": "john@example.com" })]);
+    /// ```
+    pub fn highlight(
+        self,
+        query: &QuerySearch,
+        fields: &[impl AsRef<str>],
+        pre: &str,
+        post: &str,
+    ) -> Page<Value, M>
+    where
+        T: Serialize,
+    {
+        let enabled = query.highlight == Some(true);
+        self.map(|item| {
+            let mut value = serde_json::to_value(item).unwrap_or_default();
+            if let (Value::Object(map), true) = (&mut value, enabled) {
+                for field in fields {
+                    if let Some(Value::String(text)) = map.get_mut(field.as_ref()) {
+                        *text = query.highlight_text(text, pre, post);
+                    }
+                }
+            }
+            value
+        })
+    }
+
+    /// Attach the metadata passed to the page, e.g. stats of the query
+    /// execution or the filters applied, serialized as `meta`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Stats {
+    ///     elapsed_ms: u64,
+    /// }
+    ///
+    /// let page = Page::from(vec![1, 2]).with_meta(Stats { elapsed_ms: 12 });
+    /// assert_eq!(serde_json::to_value(page).unwrap()["meta"]["elapsed_ms"], 12);
+    /// ```
+    pub fn with_meta<N>(mut self, meta: N) -> Page<T, N> {
+        let data = std::mem::take(&mut self.data);
+        self.with_parts(data, Some(meta))
+    }
+
+    /// Build a page with the data and the metadata passed,
+    /// keeping the rest of the fields.
+    fn with_parts<U, N>(self, data: Vec<U>, meta: Option<N>) -> Page<U, N> {
+        Page {
+            data,
+            offset: self.offset,
+            page_size: self.page_size,
+            total: self.total,
+            message: self.message,
+            warning: self.warning,
+            links: self.links,
+            total_pages: self.total_pages,
+            current_page: self.current_page,
+            has_next: self.has_next,
+            facets: self.facets,
+            meta,
         }
     }
 }
+
+impl<T: Serialize, M: Serialize> Page<T, M> {
+    /// Build a strong entity tag with a stable hash of the serialized
+    /// page, to be returned in the `ETag` header, so clients and proxies
+    /// can cache the results and revalidate them with `If-None-Match`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use actix_web::http::header::ETag;
+    /// use actix_web::HttpResponse;
+    ///
+    /// let page = Page::from(vec![1, 2, 3]);
+    /// assert_eq!(page.etag(), Page::from(vec![1, 2, 3]).etag());
+    /// assert_ne!(page.etag(), Page::from(vec![1, 2]).etag());
+    /// let res = HttpResponse::Ok().insert_header(ETag(page.etag())).json(page);
+    /// ```
+    pub fn etag(&self) -> EntityTag {
+        let body = serde_json::to_vec(self).unwrap_or_default();
+        EntityTag::new_strong(format!("{:016x}", stable_hash(&body)))
+    }
+}
+
+#[cfg(feature = "jsonapi")]
+impl<T: Serialize, M: Serialize> Page<T, M> {
+    /// Build a [JSON:API](https://jsonapi.org/) document with the page: the
+    /// items as resource objects of the type passed (the `id` of the items
+    /// as the resource `id`, and the rest of the fields as the `attributes`),
+    /// the total and the rest of the metadata in `meta`, and the
+    /// navigation links (see [`Page::with_links()`]) in `links`.
+    ///
+    /// Only available when the `jsonapi` feature is activated.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use serde_json::json;
+    ///
+    /// let page = Page::from(vec![json!({ "id": 1, "name": "John" })]);
+    /// assert_eq!(
+    ///     page.to_jsonapi("users"),
+    ///     json!({
+    ///         "data": [{ "type": "users", "id": "1", "attributes": { "name": "John" } }],
+    ///         "meta": { "offset": 0, "page_size": 1, "total": 1 }
+    ///     })
+    /// );
+    /// ```
+    pub fn to_jsonapi(&self, resource_type: &str) -> serde_json::Value {
+        let data: Vec<serde_json::Value> = self
+            .data
+            .iter()
+            .map(|item| {
+                let mut attributes = serde_json::to_value(item).unwrap_or_default();
+                let id = attributes.as_object_mut().and_then(|a| a.remove("id"));
+                let mut resource = serde_json::Map::new();
+                resource.insert("type".to_owned(), resource_type.into());
+                if let Some(id) = id {
+                    let id = match id {
+                        serde_json::Value::String(id) => id,
+                        id => id.to_string(),
+                    };
+                    resource.insert("id".to_owned(), id.into());
+                }
+                resource.insert("attributes".to_owned(), attributes);
+                resource.into()
+            })
+            .collect();
+        let mut meta = serde_json::to_value(PageMeta(self)).unwrap_or_default();
+        let links = meta.as_object_mut().and_then(|m| m.remove("links"));
+        let mut document = serde_json::json!({ "data": data, "meta": meta });
+        if let Some(links) = links {
+            document["links"] = links;
+        }
+        document
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<T: Serialize, M> Page<T, M> {
+    /// Serialize the data of the page as CSV, with a header row with the
+    /// names of the fields of the items, e.g. to export the page
+    /// from admin panels. Nested fields are not supported.
+    ///
+    /// Only available when the `csv` feature is activated.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::Page;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: i64,
+    ///     name: &'static str,
+    /// }
+    ///
+    /// let page = Page::from(vec![User { id: 1, name: "John" }, User { id: 2, name: "Mary" }]);
+    /// assert_eq!(page.to_csv().unwrap(), "id,name\n1,John\n2,Mary\n");
+    /// ```
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for item in &self.data {
+            writer.serialize(item).map_err(|e| AppError::Unexpected(e.into()))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| AppError::Unexpected(anyhow::anyhow!(e.to_string())))?;
+        String::from_utf8(bytes).map_err(|e| AppError::Unexpected(e.into()))
+    }
+
+    /// Build a response to download the data of the page as a CSV
+    /// file with the name passed, see [`Page::to_csv()`].
+    ///
+    /// Only available when the `csv` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// #[get("/users/export")]
+    /// async fn export(app: Data<AppState>, query: Query<QuerySearch>) -> HttpResult {
+    ///     let page = User::find(&app, &query).await?;
+    ///     page.to_csv_response("users.csv")
+    /// }
+    /// ```
+    pub fn to_csv_response(&self, filename: &str) -> Result<HttpResponse> {
+        Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(filename.to_owned())],
+            })
+            .body(self.to_csv()?))
+    }
+}
+
+#[cfg(feature = "sqlx-postgres")]
+impl<T> Page<T>
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+{
+    /// Fetch the page with the data query passed, binding the `page_size`
+    /// and the `offset` of the query search as the last two arguments
+    /// (the `LIMIT` and the `OFFSET`). The count query is only executed
+    /// if the total was requested (`include_total=true`), otherwise the
+    /// page is fetched with [`Page::fetch_lookahead()`]. The hint of
+    /// [`set_empty_page_hint()`] is set if nothing matches the search.
+    ///
+    /// Only available when the `sqlx-postgres` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::page::Page;
+    /// // ...
+    /// let page: Page<Customer> = Page::fetch(
+    ///     &mut tx,
+    ///     sqlx::query_as("SELECT * FROM customers WHERE name ILIKE $1 ORDER BY name LIMIT $2 OFFSET $3")
+    ///         .bind(format!("%{q}%")),
+    ///     sqlx::query_scalar("SELECT count(*) FROM customers WHERE name ILIKE $1")
+    ///         .bind(format!("%{q}%")),
+    ///     &query,
+    /// )
+    /// .await?;
+    /// ```
+    pub async fn fetch<'q>(
+        tx: &mut Tx<'_>,
+        data_query: QueryAs<'q, Postgres, T, PgArguments>,
+        count_query: QueryScalar<'q, Postgres, i64, PgArguments>,
+        query: &QuerySearch,
+    ) -> Result<Page<T>> {
+        if query.include_total != Some(true) {
+            return Self::fetch_lookahead(tx, data_query, query).await;
+        }
+        let data = data_query
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await?;
+        let total = count_query.fetch_one(&mut **tx).await?;
+        Ok(Page::with_data(data, Some(total), query.offset).with_hint(query))
+    }
+
+    /// Fetch the page with the data query passed without counting the
+    /// results, binding the `page_size` plus one and the `offset` of the
+    /// query search as the last two arguments (the `LIMIT` and the `OFFSET`),
+    /// so the extra row fetched sets `has_next`, see [`Page::from_lookahead()`].
+    /// The hint of [`set_empty_page_hint()`] is set if nothing matches the search.
+    ///
+    /// Only available when the `sqlx-postgres` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::page::Page;
+    /// // ...
+    /// let page: Page<Customer> = Page::fetch_lookahead(
+    ///     &mut tx,
+    ///     sqlx::query_as("SELECT * FROM customers WHERE name ILIKE $1 ORDER BY name LIMIT $2 OFFSET $3")
+    ///         .bind(format!("%{q}%")),
+    ///     &query,
+    /// )
+    /// .await?;
+    /// ```
+    pub async fn fetch_lookahead<'q>(
+        tx: &mut Tx<'_>,
+        data_query: QueryAs<'q, Postgres, T, PgArguments>,
+        query: &QuerySearch,
+    ) -> Result<Page<T>> {
+        let data = data_query
+            .bind(query.page_size.saturating_add(1))
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await?;
+        Ok(Page::from_lookahead(data, query.page_size, query.offset).with_hint(query))
+    }
+}
+
+impl PageLinks {
+    fn build(
+        req: &HttpRequest,
+        offset: i64,
+        page_size: i64,
+        total: Option<i64>,
+        len: usize,
+        has_next: Option<bool>,
+    ) -> Self {
+        let page_size = page_size.max(1);
+        let offset = offset.max(0);
+        let link = |offset: i64| page_link(req, offset, page_size);
+        let has_next = has_next_page(offset, len as i64, page_size, total, has_next);
+        PageLinks {
+            current: link(offset),
+            first: link(0),
+            prev: (offset > 0).then(|| link((offset - page_size).max(0))),
+            next: has_next.then(|| link(offset + page_size)),
+            last: total.map(|total| link((total - 1).max(0) / page_size * page_size)),
+        }
+    }
+}
+
+/// Whether there are more results after the page with the offset and the
+/// number of items passed: if the total is known, whether it's greater
+/// than the items up to the end of the page, otherwise the `has_next`
+/// already known if any, e.g. by a lookahead, or whether the page is full.
+pub(crate) fn has_next_page(
+    offset: i64,
+    len: i64,
+    page_size: i64,
+    total: Option<i64>,
+    has_next: Option<bool>,
+) -> bool {
+    match total {
+        Some(total) => offset + len < total,
+        None => has_next.unwrap_or(len >= page_size),
+    }
+}
+
+/// Build the `Link` header ([RFC 5988](https://www.rfc-editor.org/rfc/rfc5988))
+/// with the navigation links of the page, as absolute URLs, e.g.
+/// `<https://example.com/users?offset=20&page_size=10>; rel="next", ...`,
+/// for clients that follow GitHub-style pagination headers.
+///
+/// The links set with [`Page::with_links()`] are used if any, otherwise
+/// they are built with the offset of the page, so requests with the `page`
+/// argument (see [`QuerySearch::apply_page()`]) get the right links too,
+/// and the `page_size` argument of the query string of the request.
+///
+/// ```
+/// use actix_contrib_rest::page::{link_header, Page};
+/// use actix_web::http::header::LINK;
+/// use actix_web::test::TestRequest;
+/// use actix_web::HttpResponse;
+///
+/// let req = TestRequest::get().uri("/users?page_size=2").to_http_request();
+/// let page = Page::with_data(vec![1, 2], Some(3), 0);
+/// let res = HttpResponse::Ok()
+///     .insert_header((LINK, link_header(&page, &req)))
+///     .json(page);
+/// assert_eq!(
+///     res.headers().get(LINK).unwrap(),
+///     "<http://localhost:8080/users?offset=0&page_size=2>; rel=\"first\", \
+///      <http://localhost:8080/users?offset=2&page_size=2>; rel=\"next\", \
+///      <http://localhost:8080/users?offset=2&page_size=2>; rel=\"last\""
+/// );
+///
+/// // The third page requested by number
+/// let req = TestRequest::get().uri("/users?page=3&page_size=2").to_http_request();
+/// let page = Page::with_data(vec![5, 6], Some(10), 4);
+/// assert_eq!(
+///     link_header(&page, &req),
+///     "<http://localhost:8080/users?offset=0&page_size=2>; rel=\"first\", \
+///      <http://localhost:8080/users?offset=2&page_size=2>; rel=\"prev\", \
+///      <http://localhost:8080/users?offset=6&page_size=2>; rel=\"next\", \
+///      <http://localhost:8080/users?offset=8&page_size=2>; rel=\"last\""
+/// );
+/// ```
+pub fn link_header<T, M>(page: &Page<T, M>, req: &HttpRequest) -> HeaderValue {
+    let links = page.links.clone().unwrap_or_else(|| {
+        let page_size = match web::Query::<QuerySearch>::from_query(req.query_string()) {
+            Ok(query) => query.page_size,
+            Err(_) => page.page_size,
+        };
+        PageLinks::build(
+            req,
+            page.offset,
+            page_size,
+            page.total,
+            page.data.len(),
+            page.has_next,
+        )
+    });
+    let conn = req.connection_info();
+    let base = format!("{}://{}", conn.scheme(), conn.host());
+    let value = [
+        ("first", Some(&links.first)),
+        ("prev", links.prev.as_ref()),
+        ("next", links.next.as_ref()),
+        ("last", links.last.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(rel, link)| link.map(|link| format!("<{base}{link}>; rel=\"{rel}\"")))
+    .collect::<Vec<_>>()
+    .join(", ");
+    HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+/// Set the pagination headers `X-Total-Count` (if the total is known),
+/// `X-Offset` and `X-Page-Size` in the response, for header-based clients
+/// like admin UIs and datatables.
+///
+/// ```
+/// use actix_contrib_rest::page::{apply_headers, Page};
+/// use actix_web::HttpResponse;
+///
+/// let page = Page::with_data(vec![1, 2], Some(12), 10);
+/// let mut res = HttpResponse::Ok();
+/// apply_headers(&mut res, &page);
+/// let res = res.json(page);
+/// assert_eq!(res.headers().get("x-total-count").unwrap(), "12");
+/// assert_eq!(res.headers().get("x-offset").unwrap(), "10");
+/// assert_eq!(res.headers().get("x-page-size").unwrap(), "2");
+/// ```
+pub fn apply_headers<T, M>(res: &mut HttpResponseBuilder, page: &Page<T, M>) {
+    if let Some(total) = page.total {
+        res.insert_header(("X-Total-Count", total.to_string()));
+    }
+    res.insert_header(("X-Offset", page.offset.to_string()));
+    res.insert_header(("X-Page-Size", page.page_size.to_string()));
+}
+
+/// Build the URL of the request with the `offset` and `page_size`
+/// arguments passed, keeping the rest of the query string
+/// but the `page` number, replaced by the offset.
+fn page_link(req: &HttpRequest, offset: i64, page_size: i64) -> String {
+    let mut args: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|arg| !arg.is_empty())
+        .filter(|arg| {
+            let name = arg.split('=').next().unwrap_or_default();
+            !matches!(name, "offset" | "page_size" | "page")
+        })
+        .map(String::from)
+        .collect();
+    args.push(format!("offset={offset}"));
+    args.push(format!("page_size={page_size}"));
+    format!("{}?{}", req.path(), args.join("&"))
+}
+
+/// Struct used to serialize and deserialize paginated results
+/// with cursor-based (keyset) pagination, where the pages are
+/// requested with the cursor of the previous page instead of
+/// an offset, which is faster on large tables.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CursorPage<T> {
+    /// The data in the page, an empty `[]` vector
+    /// if there is no results.
+    pub data: Vec<T>,
+    /// The size of the current page result, that could
+    /// be <= to the size requested.
+    pub page_size: i64,
+    /// The cursor to request the next page, if there are more results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// The cursor to request the previous page, if this is
+    /// not the first page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev_cursor: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// Create empty page.
+    pub fn empty() -> CursorPage<T> {
+        CursorPage {
+            data: Vec::new(),
+            page_size: 0,
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+
+    /// Create the page from the rows of a keyset query, that has to fetch
+    /// `page_size + 1` rows (e.g. `WHERE id > $1 ORDER BY id LIMIT $2 + 1`)
+    /// to know whether there is a next page. The extra row is not
+    /// included in the page. The cursors are built from the first and
+    /// the last rows of the page with the `cursor` function, and the
+    /// previous cursor is only set if the page was requested with
+    /// a cursor (`after`).
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::CursorPage;
+    ///
+    /// let rows = vec![11, 12, 13];    // Fetched with a limit of 2 + 1 after the id 10
+    /// let page = CursorPage::from_keyset(rows, 2, Some("10"), |id| id.to_string());
+    /// assert_eq!(page.data, vec![11, 12]);
+    /// assert_eq!(page.next_cursor.as_deref(), Some("12"));
+    /// assert_eq!(page.prev_cursor.as_deref(), Some("11"));
+    ///
+    /// let page = CursorPage::from_keyset(vec![13], 2, Some("12"), |id| id.to_string());
+    /// assert_eq!(page.next_cursor, None);
+    /// ```
+    pub fn from_keyset(
+        mut rows: Vec<T>,
+        page_size: i64,
+        after: Option<&str>,
+        cursor: impl Fn(&T) -> String,
+    ) -> Self {
+        let has_next = rows.len() as i64 > page_size;
+        rows.truncate(page_size.max(0) as usize);
+        CursorPage {
+            next_cursor: if has_next { rows.last().map(&cursor) } else { None },
+            prev_cursor: after.and(rows.first().map(&cursor)),
+            page_size: rows.len() as i64,
+            data: rows,
+        }
+    }
+}
+
+/// Struct used to serialize the results of bulk operations, e.g. bulk
+/// create or update endpoints, with the outcome of each item: the data
+/// if the operation succeeded, or the error otherwise.
+///
+/// As [`Responder`], the result is rendered as `HTTP 207 Multi-Status`.
+///
+/// # Example
+/// ```ignore, no_run
+/// use actix_contrib_rest::page::BatchResult;
+/// // ...
+/// #[post("/users/bulk")]
+/// async fn bulk_create(app: Data<AppState>, users: Json<Vec<UserPayload>>) -> impl Responder {
+///     let mut result = BatchResult::new();
+///     for (index, user) in users.into_inner().into_iter().enumerate() {
+///         match User::create(&app, &user).await {
+///             Ok(user) => result.push_ok(index, Some(user.id.to_string()), user),
+///             Err(e) => result.push_err(index, None, &e),
+///         }
+///     }
+///     result
+/// }
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchResult<T> {
+    /// The outcome of each item.
+    pub results: Vec<BatchItem<T>>,
+    /// The number of items that succeeded.
+    pub succeeded: usize,
+    /// The number of items that failed.
+    pub failed: usize,
+}
+
+/// The outcome of an item of a bulk operation, see [`BatchResult`].
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchItem<T> {
+    /// The position of the item in the request.
+    pub index: usize,
+    /// The ID of the resource, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The status code of the operation of the item.
+    pub status: u16,
+    /// The data of the resource, if the operation succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    /// The error, if the operation failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ValidationErrorPayload>,
+}
+
+impl<T> Default for BatchResult<T> {
+    fn default() -> Self {
+        BatchResult {
+            results: Vec::new(),
+            succeeded: 0,
+            failed: 0,
+        }
+    }
+}
+
+impl<T> BatchResult<T> {
+    /// Create an empty result.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the outcome of an item that succeeded, with the `200 OK` status.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::BatchResult;
+    /// use actix_contrib_rest::result::AppError;
+    ///
+    /// let mut result = BatchResult::new();
+    /// result.push_ok(0, Some("1".to_owned()), "John");
+    /// result.push_err(1, None, &AppError::Validation(Some("invalid_name"), "Invalid name".to_owned()));
+    /// assert_eq!(result.succeeded, 1);
+    /// assert_eq!(result.failed, 1);
+    /// assert_eq!(result.results[1].status, 400);
+    /// assert_eq!(result.results[1].error.as_ref().unwrap().code.as_deref(), Some("invalid_name"));
+    /// ```
+    pub fn push_ok(&mut self, index: usize, id: Option<String>, data: T) {
+        self.succeeded += 1;
+        self.results.push(BatchItem {
+            index,
+            id,
+            status: StatusCode::OK.as_u16(),
+            data: Some(data),
+            error: None,
+        });
+    }
+
+    /// Add the outcome of an item that failed, with the status and the
+    /// payload of the error (see [`AppError::payload()`]), including the
+    /// field errors if any. The message of internal errors is not exposed.
+    ///
+    /// ```
+    /// use actix_contrib_rest::page::BatchResult;
+    /// use actix_contrib_rest::result::{AppError, ValidationErrorPayload};
+    /// use actix_web::http::StatusCode;
+    /// use serde_json::json;
+    ///
+    /// let mut result = BatchResult::<String>::new();
+    /// result.push_err(0, None, &AppError::Custom(
+    ///     StatusCode::UNPROCESSABLE_ENTITY,
+    ///     ValidationErrorPayload::with_code("validation_error".to_owned(), "Validation error".to_owned())
+    ///         .field("email", "email", "Invalid email", json!({ "value": "jhon" })),
+    /// ));
+    /// assert_eq!(result.results[0].status, 422);
+    /// let field_errors = result.results[0].error.as_ref().unwrap().field_errors.as_ref().unwrap();
+    /// assert_eq!(field_errors["email"][0].message.as_deref(), Some("Invalid email"));
+    /// ```
+    pub fn push_err(&mut self, index: usize, id: Option<String>, error: &AppError) {
+        let status = error.status_code();
+        let payload = error.payload();
+        self.failed += 1;
+        self.results.push(BatchItem {
+            index,
+            id,
+            status: status.as_u16(),
+            data: None,
+            error: Some(payload),
+        });
+    }
+}
+
+impl<T: Serialize> Responder for BatchResult<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::build(StatusCode::MULTI_STATUS).json(self)
+    }
+}
+
+/// Build a response that streams the items passed as
+/// [NDJSON](https://github.com/ndjson/ndjson-spec) (`application/x-ndjson`),
+/// one JSON document per line, in a chunked body, e.g. for export endpoints
+/// where building a full [`Page`] in memory is not viable.
+///
+/// If an item fails, the response is aborted, because the
+/// status was already sent to the client.
+///
+/// # Example
+/// ```ignore, no_run
+/// use actix_contrib_rest::page::ndjson_stream;
+/// // ...
+/// #[get("/export")]
+/// async fn export(app: Data<AppState>) -> HttpResponse {
+///     let stream = sqlx::query_as::<_, Customer>("SELECT * FROM customers")
+///         .fetch(app.pool.as_ref().unwrap());
+///     ndjson_stream(stream)
+/// }
+/// ```
+pub fn ndjson_stream<S, T, E>(stream: S) -> HttpResponse
+where
+    S: Stream<Item = core::result::Result<T, E>> + Unpin + 'static,
+    T: Serialize,
+    E: Into<AppError>,
+{
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(NdjsonStream { inner: stream })
+}
+
+/// Stream of the lines of a NDJSON body, see [`ndjson_stream()`].
+struct NdjsonStream<S> {
+    inner: S,
+}
+
+impl<S, T, E> Stream for NdjsonStream<S>
+where
+    S: Stream<Item = core::result::Result<T, E>> + Unpin,
+    T: Serialize,
+    E: Into<AppError>,
+{
+    type Item = core::result::Result<Bytes, AppError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx).map(|item| {
+            item.map(|result| -> Self::Item {
+                let mut line = serde_json::to_vec(&result.map_err(Into::<AppError>::into)?)?;
+                line.push(b'\n');
+                Ok(Bytes::from(line))
+            })
+        })
+    }
+}
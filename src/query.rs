@@ -1,12 +1,122 @@
 //! Map query searches.
 
-use serde::Deserialize;
-use validator::Validate;
+use crate::result::{AppError, ErrorCode, Result as AppResult, ValidationErrorPayload};
+
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpRequest};
+#[cfg(feature = "cursor")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+#[cfg(feature = "cursor")]
+use base64::Engine;
+#[cfg(feature = "cursor")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "cursor")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "cursor")]
+use serde::Serialize;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+#[cfg(feature = "cursor")]
+use sha2::Sha256;
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::{Postgres, QueryBuilder};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 fn default_page_size() -> i64 {
     50
 }
 
+/// Deserialize a pagination number (offset, page size, total...)
+/// rejecting negative values, so they cannot flow into SQL
+/// queries even if the struct is not validated.
+pub(crate) fn deserialize_non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let value = i64::deserialize(deserializer)?;
+    if value < 0 {
+        return Err(D::Error::custom(format!("invalid value {value}, expected a non-negative number")));
+    }
+    Ok(value)
+}
+
+/// Same as [`deserialize_non_negative()`] but for optional values.
+pub(crate) fn deserialize_non_negative_opt<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    match Option::<i64>::deserialize(deserializer)? {
+        Some(value) if value < 0 => {
+            Err(D::Error::custom(format!("invalid value {value}, expected a non-negative number")))
+        }
+        value => Ok(value),
+    }
+}
+
+/// Deserialize a comma separated list of values, e.g. `?ids=1,2,3`, into
+/// a vector of any type implementing [`FromStr`], like `String`, `i64`
+/// or `Uuid`. The empty values are skipped, so an empty string is
+/// deserialized into an empty vector. Use it along `#[serde(default)]`
+/// so the argument is optional.
+///
+/// ```
+/// use actix_contrib_rest::query::{deserialize_comma_separated, deserialize_comma_separated_opt};
+/// use actix_web::web::Query;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Params {
+///     #[serde(default, deserialize_with = "deserialize_comma_separated")]
+///     ids: Vec<i64>,
+///     #[serde(default, deserialize_with = "deserialize_comma_separated_opt")]
+///     tags: Option<Vec<String>>,
+/// }
+///
+/// let params = Query::<Params>::from_query("ids=1,,2,3&tags=").unwrap();
+/// assert_eq!(params.ids, &[1, 2, 3]);
+/// assert_eq!(params.tags, Some(vec![]));
+/// let params = Query::<Params>::from_query("").unwrap();
+/// assert!(params.ids.is_empty() && params.tags.is_none());
+/// assert!(Query::<Params>::from_query("ids=1,two").is_err());
+/// ```
+pub fn deserialize_comma_separated<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<T>().map_err(|e| D::Error::custom(format!("invalid value \"{v}\": {e}"))))
+        .collect()
+}
+
+/// Same as [`deserialize_comma_separated()`] but for optional arguments,
+/// so it can be told whether the argument was set.
+pub fn deserialize_comma_separated_opt<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    deserialize_comma_separated(deserializer).map(Some)
+}
+
+/// Hash of the bytes passed with the 64-bit FNV-1a algorithm, that
+/// unlike the `std` hashers is stable across Rust versions and
+/// processes, so it can be shared across services and restarts.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
 /// Struct used to deserialize with `serde` query strings
 /// from a request URL.
 ///
@@ -15,78 +125,2469 @@ fn default_page_size() -> i64 {
 /// - `/api/users?q=marian&include_total=true`
 /// - `/api/v1/sales?q=customer:john&page_size=100`
 /// - `/some-endpoint?page_size=20&sort=-name`
+/// - `/api/v1/orders?filter=status:eq:open,amount:gte:100`
+/// - `/api/products?page=3&page_size=20`
+/// - `/api/reports/sales?group_by=status,day&distinct=true`
+/// - `/api/articles?q=rust%20actix&highlight=true`
 ///
 /// When an instance is created through serde,
 /// the `page_size` attribute is set to `50`
 /// if the stream serialized does not have the
-/// value set.
-#[derive(Debug, Clone, Deserialize, Validate, PartialEq, Eq)]
+/// value set, and negative `offset` and `page_size`
+/// values are rejected.
+///
+/// ```
+/// use actix_contrib_rest::query::QuerySearch;
+/// use actix_web::web::Query;
+///
+/// let query = Query::<QuerySearch>::from_query("q=john&offset=20").unwrap();
+/// assert_eq!(query.page_size, 50);
+/// assert!(Query::<QuerySearch>::from_query("offset=-10").is_err());
+/// ```
+#[derive(Debug, Clone, Deserialize, Validate, PartialEq, Eq, Hash)]
 pub struct QuerySearch {
     pub q: Option<String>,
     pub sort: Option<String>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_non_negative")]
     #[validate(range(min = 0))]
     pub offset: i64,
-    #[serde(default = "default_page_size")]
+    #[serde(default = "default_page_size", deserialize_with = "deserialize_non_negative")]
     #[validate(range(min = 1))]
     pub page_size: i64,
+    /// The number of the page (1-indexed), an alternative to the `offset`
+    /// for clients that only speak page numbers, see [`QuerySearch::apply_page()`].
+    #[serde(default, deserialize_with = "deserialize_non_negative_opt")]
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
     pub include_total: Option<bool>,
+    /// Comma separated list of filters with the form `field:op:value`,
+    /// see [`QuerySearch::parse_filters()`].
+    pub filter: Option<String>,
+    /// Comma separated list of the columns to group the results by,
+    /// see [`QuerySearch::group_by_clause()`].
+    pub group_by: Option<String>,
+    /// Whether the duplicated rows are removed,
+    /// see [`QuerySearch::select_keyword()`].
+    pub distinct: Option<bool>,
+    /// Whether the terms of the search are highlighted in the results,
+    /// see [`QuerySearch::highlight_text()`].
+    pub highlight: Option<bool>,
+}
+
+impl Default for QuerySearch {
+    fn default() -> Self {
+        QuerySearch {
+            q: None,
+            sort: None,
+            offset: 0,
+            page_size: default_page_size(),
+            page: None,
+            include_total: None,
+            filter: None,
+            group_by: None,
+            distinct: None,
+            highlight: None,
+        }
+    }
 }
 
 impl QuerySearch {
-    /// Parse sort argument "col1,col2,-col3..." into a vector of strings,
-    /// and if the column name starts with "-", it's translated to a DESC
-    /// keyword, e.g. "-name" --> "name DESC".
+    /// Set the offset from the page number, if set, with the form
+    /// `(page - 1) * page_size`. It's called by the [`QuerySearch`]
+    /// extractor, that also rejects the queries with both the
+    /// `page` and the `offset` arguments.
+    ///
+    /// Pages lower than 1, or so high that the offset overflows, are
+    /// rejected with an `HTTP 400 Bad Request` error, with a `range`
+    /// entry in `field_errors` for `page`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let mut q = QuerySearch { page: Some(3), page_size: 20, ..Default::default() };
+    /// q.apply_page().unwrap();
+    /// assert_eq!(q.offset, 40);
+    /// assert!(QuerySearch { page: Some(i64::MAX), ..q.clone() }.apply_page().is_err());
+    /// assert!(QuerySearch { page: Some(0), ..q }.apply_page().is_err());
+    /// ```
+    pub fn apply_page(&mut self) -> AppResult<()> {
+        let page = match self.page {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        let offset = page
+            .checked_sub(1)
+            .filter(|p| *p >= 0)
+            .and_then(|p| p.checked_mul(self.page_size));
+        match offset {
+            Some(offset) => {
+                self.offset = offset;
+                Ok(())
+            }
+            None => Err(invalid_argument(
+                "Invalid query string",
+                "page",
+                ErrorCode::Range.as_str(),
+                "The page is out of range",
+                json!({ "min": 1, "value": page }),
+            )),
+        }
+    }
+
+    /// Parse sort argument "col1,col2,-col3..." into a vector of [`SortField`],
+    /// and if the column name starts with "-", the direction is descending,
+    /// e.g. "-name" --> "name DESC" when formatted as SQL. The position of
+    /// the null values can be set with the `!nulls_first` and `!nulls_last`
+    /// suffixes, e.g. "-ended_at!nulls_last" --> "ended_at DESC NULLS LAST".
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::{Direction, QuerySearch, SortField};
+    /// let sql = |sort: Vec<SortField>| sort.iter().map(ToString::to_string).collect::<Vec<_>>();
+    /// let q = QuerySearch { page_size: 10, ..Default::default() };
+    /// assert_eq!(q.parse_sort(&["a", "b"]), Vec::<SortField>::new());
+    /// let q = QuerySearch { page_size: 10, sort: Some(String::from("a,-b")), ..Default::default() };
+    /// assert_eq!(sql(q.parse_sort(&["a", "b"])), &["a", "b DESC"]);
+    /// assert_eq!(q.parse_sort(&["a", "b"])[1].direction, Direction::Desc);
+    /// let q = QuerySearch { page_size: 10, sort: Some(String::from("name,-b,c")), ..Default::default() };
+    /// assert_eq!(sql(q.parse_sort(&vec!["name", "c"])), &["name", "c"]);
+    /// let q = QuerySearch { sort: Some(String::from("-ended_at!nulls_last,a!nulls_first,b!x")), ..Default::default() };
+    /// assert_eq!(sql(q.parse_sort(&["a", "b", "ended_at"])), &["ended_at DESC NULLS LAST", "a NULLS FIRST"]);
+    /// ```
+    pub fn parse_sort(&self, allowed_fields: &[&str]) -> Vec<SortField> {
+        self.parse_sort_with(|field| allowed_fields.iter().find(|f| **f == field).copied())
+    }
+
+    /// Same as [`QuerySearch::parse_sort()`], but the allowed fields are the
+    /// keys of the mapping passed, translated into their values: the DB
+    /// column or expression to sort by, so the names of the fields exposed
+    /// by the API don't have to match the names of the columns.
     ///
     /// ```
     /// use actix_contrib_rest::query::QuerySearch;
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: None, include_total: None };
-    /// assert_eq!(q.parse_sort(&["a", "b"]), Vec::<String>::new());
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("a,-b")), include_total: None };
-    /// assert_eq!(q.parse_sort(&["a", "b"]), &[String::from("a"), String::from("b DESC")]);
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("name,-b,c")), include_total: None };
-    /// assert_eq!(q.parse_sort(&vec!["name", "c"]), &[String::from("name"), String::from("c")]);
+    /// let mapping = [("createdAt", "created_at"), ("name", "lower(name)")];
+    /// let q = QuerySearch { sort: Some(String::from("-createdAt,name,id")), ..Default::default() };
+    /// let sort = q.parse_sort_mapped(&mapping);
+    /// assert_eq!((sort[0].to_string(), sort[1].to_string()), ("created_at DESC".into(), "lower(name)".into()));
     /// ```
-    pub fn parse_sort(&self, allowed_fields: &[&str]) -> Vec<String> {
+    pub fn parse_sort_mapped(&self, mapping: &[(&str, &str)]) -> Vec<SortField> {
+        self.parse_sort_with(|field| mapping.iter().find(|(f, _)| *f == field).map(|(_, c)| *c))
+    }
+
+    /// Parse the sort argument translating each field with the function
+    /// passed into the column to sort by, dropping the fields without column.
+    fn parse_sort_with<'a>(&self, column: impl Fn(&str) -> Option<&'a str>) -> Vec<SortField> {
         self.sort
             .as_deref()
             .unwrap_or("")
             .split(',')
-            .filter(|s| allowed_fields.contains(&s.strip_prefix('-').unwrap_or(s)))
-            .map(|f| {
-                f.strip_prefix('-')
-                    .map(|d| format!("{d} DESC"))
-                    .unwrap_or(f.to_string())
+            .filter_map(SortField::parse)
+            .filter_map(|sort| {
+                let field = column(&sort.field)?.to_owned();
+                Some(SortField { field, ..sort })
             })
             .collect()
     }
 
+    /// Same as [`QuerySearch::parse_sort()`], but instead of silently dropping
+    /// the columns not included in the allowed fields, that hides typos
+    /// of the clients, an `HTTP 400 Bad Request` error is returned with a
+    /// `field_errors` entry for `sort` listing the rejected columns and
+    /// the allowed ones.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { sort: Some(String::from("name,-created_at")), ..Default::default() };
+    /// assert_eq!(q.parse_sort_strict(&["name", "created_at"]).unwrap().len(), 2);
+    /// assert!(q.parse_sort_strict(&["name"]).is_err());
+    /// ```
+    pub fn parse_sort_strict(&self, allowed_fields: &[&str]) -> AppResult<Vec<SortField>> {
+        self.check_sort(allowed_fields)?;
+        Ok(self.parse_sort(allowed_fields))
+    }
+
+    /// Check the columns of the sort argument are included in the allowed
+    /// fields, returning an `HTTP 400 Bad Request` error with a `field_errors`
+    /// entry for `sort` listing the rejected columns and the allowed ones.
+    fn check_sort(&self, allowed_fields: &[&str]) -> AppResult<()> {
+        let rejected: Vec<&str> = self
+            .sort
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .filter(|s| match SortField::parse(s) {
+                Some(sort) => !allowed_fields.contains(&sort.field.as_str()),
+                None => true,
+            })
+            .collect();
+        if rejected.is_empty() {
+            return Ok(());
+        }
+        Err(invalid_argument(
+            "Invalid sort argument",
+            "sort",
+            ErrorCode::InvalidSort.as_str(),
+            format!("Sorting by \"{}\" is not allowed", rejected.join(", ")),
+            json!({ "value": rejected, "allowed": allowed_fields }),
+        ))
+    }
+
     /// Parse sort argument "col1,col2,-col3..." into a compatible SQL `ORDER BY` expression,
     /// e.g. `name,-age` --> `name, age DESC`, to be concatenated in a SQL `SELECT` query.
     ///
     /// ```
     /// use actix_contrib_rest::query::QuerySearch;
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: None, include_total: None };
+    /// let q = QuerySearch { page_size: 10, ..Default::default() };
     /// assert_eq!(q.sort_as_order_by_args(&["a", "b"], "a"), "a");
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("a,-b")), include_total: None };
+    /// let q = QuerySearch { page_size: 10, sort: Some(String::from("a,-b")), ..Default::default() };
     /// assert_eq!(q.sort_as_order_by_args(&["a", "b"], "a"), "a, b DESC");
-    /// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("name,-b,c")), include_total: None };
+    /// let q = QuerySearch { page_size: 10, sort: Some(String::from("name,-b,c")), ..Default::default() };
     /// assert_eq!(q.sort_as_order_by_args(&["a", "h"], "c"), "c");
     /// ```
     pub fn sort_as_order_by_args(&self, allowed_fields: &[&str], default: &str) -> String {
-        let sorting = self.parse_sort(allowed_fields);
-        match sorting.len() {
-            0 => String::from(default),
-            _ => sorting.join(", "),
+        order_by_args(self.parse_sort(allowed_fields), default)
+    }
+
+    /// Same as [`QuerySearch::sort_as_order_by_args()`], but translating the
+    /// fields with the mapping passed, see [`QuerySearch::parse_sort_mapped()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let mapping = [("createdAt", "created_at"), ("name", "lower(name)")];
+    /// let q = QuerySearch { sort: Some(String::from("name,-createdAt")), ..Default::default() };
+    /// assert_eq!(q.sort_as_order_by_args_mapped(&mapping, "id"), "lower(name), created_at DESC");
+    /// assert_eq!(QuerySearch::default().sort_as_order_by_args_mapped(&mapping, "id"), "id");
+    /// ```
+    pub fn sort_as_order_by_args_mapped(&self, mapping: &[(&str, &str)], default: &str) -> String {
+        order_by_args(self.parse_sort_mapped(mapping), default)
+    }
+
+    /// Build the pattern to match the search argument `q` anywhere with the
+    /// SQL `LIKE` / `ILIKE` operators, e.g. `%john%`, escaping the `%` and
+    /// `_` wildcards and the backslashes of the search, so they are matched
+    /// literally instead of being injected as wildcards. `None` is returned
+    /// if the search is not set or it's blank.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { q: Some(String::from(" 100%_off ")), ..Default::default() };
+    /// assert_eq!(q.like_pattern().unwrap(), r"%100\%\_off%");
+    /// assert_eq!(q.starts_with_pattern().unwrap(), r"100\%\_off%");
+    /// assert_eq!(QuerySearch::default().like_pattern(), None);
+    /// ```
+    pub fn like_pattern(&self) -> Option<String> {
+        self.escaped_q().map(|q| format!("%{q}%"))
+    }
+
+    /// Same as [`QuerySearch::like_pattern()`], but the pattern
+    /// matches the values starting with the search, e.g. `john%`,
+    /// that unlike the other can use the B-tree indexes.
+    pub fn starts_with_pattern(&self) -> Option<String> {
+        self.escaped_q().map(|q| format!("{q}%"))
+    }
+
+    fn escaped_q(&self) -> Option<String> {
+        let q = self.q.as_deref()?.trim();
+        (!q.is_empty()).then(|| escape_like(q))
+    }
+
+    /// Split the free-text search argument `q` into the terms to search,
+    /// separated by whitespaces, except the double-quoted phrases, that are
+    /// kept as a single term. Only the letters, digits and the `-`, `.`,
+    /// `@` and `'` characters are kept, the rest are stripped.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { q: Some(String::from(r#"john  "new   york" O'Brien; drop%"#)), ..Default::default() };
+    /// assert_eq!(q.terms(), &["john", "new york", "O'Brien", "drop"]);
+    /// assert!(QuerySearch::default().terms().is_empty());
+    /// ```
+    pub fn terms(&self) -> Vec<String> {
+        let sanitize = |term: &str| -> String {
+            term.split_whitespace()
+                .map(|word| {
+                    word.chars()
+                        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '.' | '@' | '\''))
+                        .collect::<String>()
+                })
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        self.q
+            .as_deref()
+            .unwrap_or("")
+            .split('"')
+            .enumerate()
+            .flat_map(|(i, part)| match i % 2 {
+                // Inside quotes
+                1 => vec![sanitize(part)],
+                _ => part.split_whitespace().map(sanitize).collect(),
+            })
+            .filter(|term| !term.is_empty())
+            .collect()
+    }
+
+    /// Wrap the occurrences of the [terms](QuerySearch::terms) of the search
+    /// in the text passed with the `pre` and `post` markers, e.g. `<em>` and
+    /// `</em>`, matching case-insensitively and preferring the longest terms.
+    /// The text is not escaped, so HTML markers should be only used with
+    /// text already escaped. See also [`Page::highlight()`](crate::page::Page::highlight).
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { q: Some(String::from("rust act")), ..Default::default() };
+    /// assert_eq!(
+    ///     q.highlight_text("Rust web apps with Actix", "<em>", "</em>"),
+    ///     "<em>Rust</em> web apps with <em>Act</em>ix",
+    /// );
+    /// assert_eq!(QuerySearch::default().highlight_text("Rust", "[", "]"), "Rust");
+    /// ```
+    pub fn highlight_text(&self, text: &str, pre: &str, post: &str) -> String {
+        let mut terms: Vec<Vec<char>> = self.terms().iter().map(|t| t.chars().collect()).collect();
+        terms.sort_unstable_by_key(|t| std::cmp::Reverse(t.len()));
+        // Length in bytes of the term matched at the start of the text, if any
+        let match_len = |text: &str, term: &[char]| -> Option<usize> {
+            let mut chars = text.char_indices();
+            for t in term {
+                match chars.next() {
+                    Some((_, c)) if c.to_lowercase().eq(t.to_lowercase()) => {}
+                    _ => return None,
+                }
+            }
+            Some(chars.next().map_or(text.len(), |(i, _)| i))
+        };
+        let mut highlighted = String::with_capacity(text.len());
+        let mut rest = text;
+        'outer: while let Some(c) = rest.chars().next() {
+            for term in &terms {
+                if let Some(len) = match_len(rest, term) {
+                    highlighted.push_str(pre);
+                    highlighted.push_str(&rest[..len]);
+                    highlighted.push_str(post);
+                    rest = &rest[len..];
+                    continue 'outer;
+                }
+            }
+            highlighted.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        highlighted
+    }
+
+    /// Parse the qualifiers of the search argument `q`, GitHub-style,
+    /// e.g. `customer:john status:open overdue`, where the words with the
+    /// form `key:value` are qualifiers if the key is included in the allowed
+    /// keys, and the rest is the free text of the search. The values can
+    /// be double-quoted to include whitespaces, e.g. `customer:"john doe"`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch {
+    ///     q: Some(String::from(r#"customer:"john doe" status:open overdue status:pending at:10:30"#)),
+    ///     ..Default::default()
+    /// };
+    /// let search = q.parse_qualifiers(&["customer", "status"]);
+    /// assert_eq!(search.qualifiers["customer"], &["john doe"]);
+    /// assert_eq!(search.qualifiers["status"], &["open", "pending"]);
+    /// assert_eq!(search.free_text, "overdue at:10:30");
+    /// ```
+    pub fn parse_qualifiers(&self, allowed_keys: &[&str]) -> SearchQualifiers {
+        let mut search = SearchQualifiers::default();
+        let mut free_text = Vec::new();
+        for word in split_words(self.q.as_deref().unwrap_or("")) {
+            match word.split_once(':') {
+                Some((key, value)) if allowed_keys.contains(&key) && !value.is_empty() => {
+                    let value = value.trim_matches('"').to_owned();
+                    search.qualifiers.entry(key.to_owned()).or_default().push(value);
+                }
+                _ => free_text.push(word),
+            }
+        }
+        search.free_text = free_text.join(" ");
+        search
+    }
+
+    /// Sanitize the free-text search argument `q` to be passed to the
+    /// Postgres `websearch_to_tsquery()` function, keeping the words,
+    /// the double-quoted phrases, the `or` keyword and the `-` negations,
+    /// but stripping the rest of the symbols and the unbalanced quotes.
+    /// `None` is returned if there is nothing left to search.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { q: Some(String::from(r#"  "red car" or -truck & (blue):* "wheel "#)), ..Default::default() };
+    /// assert_eq!(q.to_tsquery().unwrap(), r#""red car" or -truck blue wheel"#);
+    /// let q = QuerySearch { q: Some(String::from("!&|")), ..Default::default() };
+    /// assert_eq!(q.to_tsquery(), None);
+    /// ```
+    pub fn to_tsquery(&self) -> Option<String> {
+        let q = self.q.as_deref()?;
+        let mut quotes = q.matches('"').count();
+        let mut sanitized = String::with_capacity(q.len());
+        // Drop the last quote if unbalanced
+        for c in q.chars().rev() {
+            match c {
+                '"' if quotes % 2 == 1 => quotes = 0,
+                c if c.is_alphanumeric() || matches!(c, '"' | '-' | '\'') => sanitized.push(c),
+                _ => sanitized.push(' '),
+            }
+        }
+        let sanitized: String = sanitized.chars().rev().collect();
+        let sanitized = sanitized.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!sanitized.replace('"', "").trim().is_empty()).then_some(sanitized)
+    }
+
+    /// Build the SQL condition to match the `tsvector` column passed with
+    /// the search argument, with the placeholders of the bind parameters
+    /// numbered from the number passed: first the name of the text search
+    /// configuration, e.g. `english`, and then the search, sanitized with
+    /// [`QuerySearch::to_tsquery()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// assert_eq!(
+    ///     QuerySearch::tsquery_condition("search_vector", 1),
+    ///     "search_vector @@ websearch_to_tsquery($1::regconfig, $2)"
+    /// );
+    /// ```
+    pub fn tsquery_condition(column: &str, first_param: usize) -> String {
+        let (config, search) = (first_param, first_param + 1);
+        format!("{column} @@ websearch_to_tsquery(${config}::regconfig, ${search})")
+    }
+
+    /// Parse the `group_by` argument "col1,col2..." into the columns to
+    /// group the results by, checked against the allowed fields. The
+    /// columns not allowed are rejected with an `HTTP 400 Bad Request`
+    /// error, with a `field_errors` entry for `group_by` listing the
+    /// rejected columns and the allowed ones.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { group_by: Some(String::from("status, day")), ..Default::default() };
+    /// assert_eq!(q.parse_group_by(&["status", "day"]).unwrap(), &["status", "day"]);
+    /// assert!(q.parse_group_by(&["status"]).is_err());
+    /// ```
+    pub fn parse_group_by(&self, allowed_fields: &[&str]) -> AppResult<Vec<String>> {
+        let columns: Vec<&str> = self
+            .group_by
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .collect();
+        let rejected: Vec<&str> = columns.iter().copied().filter(|c| !allowed_fields.contains(c)).collect();
+        if !rejected.is_empty() {
+            return Err(invalid_argument(
+                "Invalid group_by argument",
+                "group_by",
+                ErrorCode::InvalidGroupBy.as_str(),
+                format!("Grouping by \"{}\" is not allowed", rejected.join(", ")),
+                json!({ "value": rejected, "allowed": allowed_fields }),
+            ));
+        }
+        Ok(columns.into_iter().map(String::from).collect())
+    }
+
+    /// Build the SQL `GROUP BY` clause with the columns of the `group_by`
+    /// argument (see [`QuerySearch::parse_group_by()`]), e.g. `GROUP BY
+    /// status, day`, or `None` if the argument is not set. The columns
+    /// can be included in the `SELECT` list along the aggregations with
+    /// [`QuerySearch::parse_group_by()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { group_by: Some(String::from("status")), ..Default::default() };
+    /// let allowed = ["status", "day"];
+    /// let columns = q.parse_group_by(&allowed).unwrap().join(", ");
+    /// let sql = format!(
+    ///     "SELECT {columns}, count(*) FROM sales {}",
+    ///     q.group_by_clause(&allowed).unwrap().unwrap_or_default()
+    /// );
+    /// assert_eq!(sql, "SELECT status, count(*) FROM sales GROUP BY status");
+    /// ```
+    pub fn group_by_clause(&self, allowed_fields: &[&str]) -> AppResult<Option<String>> {
+        let columns = self.parse_group_by(allowed_fields)?;
+        Ok((!columns.is_empty()).then(|| format!("GROUP BY {}", columns.join(", "))))
+    }
+
+    /// The keyword to start the SQL `SELECT` statement with,
+    /// `SELECT DISTINCT` if the `distinct` argument is true.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { distinct: Some(true), ..Default::default() };
+    /// assert_eq!(q.select_keyword(), "SELECT DISTINCT");
+    /// assert_eq!(QuerySearch::default().select_keyword(), "SELECT");
+    /// ```
+    pub fn select_keyword(&self) -> &'static str {
+        match self.distinct {
+            Some(true) => "SELECT DISTINCT",
+            _ => "SELECT",
+        }
+    }
+
+    /// Serialize the arguments set back into a query string, percent-encoded,
+    /// e.g. to forward the search to upstream services unchanged. The page
+    /// number is not included, since it's already translated into the offset.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q = QuerySearch { q: Some(String::from("john doe")), sort: Some(String::from("-name")), ..Default::default() };
+    /// assert_eq!(q.to_query_string(), "q=john%20doe&sort=-name&offset=0&page_size=50");
+    /// assert_eq!(q.to_query_string_with_offset(100), "q=john%20doe&sort=-name&offset=100&page_size=50");
+    /// ```
+    pub fn to_query_string(&self) -> String {
+        self.to_query_string_with_offset(self.offset)
+    }
+
+    /// Same as [`QuerySearch::to_query_string()`], but with the offset
+    /// passed, e.g. to build the links to the next and previous pages.
+    pub fn to_query_string_with_offset(&self, offset: i64) -> String {
+        let mut args = Vec::new();
+        if let Some(q) = &self.q {
+            args.push(format!("q={}", percent_encode(q)));
+        }
+        if let Some(sort) = &self.sort {
+            args.push(format!("sort={}", percent_encode(sort)));
+        }
+        if let Some(filter) = &self.filter {
+            args.push(format!("filter={}", percent_encode(filter)));
+        }
+        args.push(format!("offset={offset}"));
+        args.push(format!("page_size={}", self.page_size));
+        if let Some(include_total) = self.include_total {
+            args.push(format!("include_total={include_total}"));
+        }
+        if let Some(group_by) = &self.group_by {
+            args.push(format!("group_by={}", percent_encode(group_by)));
+        }
+        if let Some(distinct) = self.distinct {
+            args.push(format!("distinct={distinct}"));
+        }
+        if let Some(highlight) = self.highlight {
+            args.push(format!("highlight={highlight}"));
+        }
+        args.join("&")
+    }
+
+    /// Build a key with the prefix passed and the [fingerprint](QuerySearch::fingerprint)
+    /// of the query, e.g. `"users:9f3b0c52a1e4d7f6"`, to cache the
+    /// results of the query in server-side caches. The same arguments
+    /// produce the same key across processes and services.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q1 = QuerySearch { q: Some("john".into()), page_size: 10, ..Default::default() };
+    /// let q2 = QuerySearch { offset: 10, ..q1.clone() };
+    /// assert!(q1.cache_key("users").starts_with("users:"));
+    /// assert_eq!(q1.cache_key("users"), q1.clone().cache_key("users"));
+    /// assert_ne!(q1.cache_key("users"), q2.cache_key("users"));
+    /// ```
+    pub fn cache_key(&self, prefix: &str) -> String {
+        format!("{prefix}:{:016x}", self.fingerprint())
+    }
+
+    /// Stable hash of the canonical form of the query, where the equivalent
+    /// queries have the same fingerprint, e.g. regardless of the order of
+    /// the filters, the whitespaces of the search, or whether the page is
+    /// requested by number or by offset. Unlike [`Hash`], it's stable across
+    /// processes and services, so it can be used as a key to cache the
+    /// responses, or to deduplicate identical queries in-flight.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// let q1 = QuerySearch {
+    ///     q: Some(String::from("john  doe")),
+    ///     filter: Some(String::from("status:eq:open,amount:gte:100")),
+    ///     page: Some(2),
+    ///     page_size: 10,
+    ///     ..Default::default()
+    /// };
+    /// let q2 = QuerySearch {
+    ///     q: Some(String::from(" john doe")),
+    ///     filter: Some(String::from("amount:gte:100, status:eq:open")),
+    ///     offset: 10,
+    ///     page_size: 10,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(q1.fingerprint(), q2.fingerprint());
+    /// assert_ne!(q1.fingerprint(), QuerySearch { sort: Some(String::from("name")), ..q1.clone() }.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let list = |value: &Option<String>, sorted: bool| {
+            let mut items: Vec<&str> = value
+                .as_deref()
+                .unwrap_or("")
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect();
+            if sorted {
+                items.sort_unstable();
+            }
+            items.join(",")
+        };
+        let mut query = self.clone();
+        // The pages out of range are kept apart from the valid offsets
+        let offset = match query.apply_page() {
+            Ok(()) => query.offset.to_string(),
+            Err(_) => format!("page:{}", query.page.unwrap_or_default()),
+        };
+        let q = query.q.as_deref().unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ");
+        let canonical = format!(
+            "q={q}\0sort={}\0filter={}\0group_by={}\0distinct={}\0highlight={}\0offset={}\0page_size={}\0include_total={}",
+            list(&query.sort, false),
+            list(&query.filter, true),
+            list(&query.group_by, false),
+            query.distinct.unwrap_or(false),
+            query.highlight.unwrap_or(false),
+            offset,
+            query.page_size,
+            query.include_total.unwrap_or(false),
+        );
+        stable_hash(canonical.as_bytes())
+    }
+
+    /// Parse the filter argument "field1:op:value1,field2:op:value2..." into
+    /// a vector of [`Filter`], e.g. `status:eq:open,amount:gte:100`. The value
+    /// is everything after the operator, so it can contain `:` characters.
+    ///
+    /// Filters on fields not included in the allowed fields, with unknown
+    /// operators (see [`FilterOp`]) or malformed are rejected with an
+    /// `HTTP 400 Bad Request` error, with a `field_errors` entry
+    /// for `filter` for each invalid filter.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::{Filter, FilterOp, QuerySearch};
+    /// let q = QuerySearch { filter: Some(String::from("status:eq:open,amount:gte:100")), ..Default::default() };
+    /// assert_eq!(q.parse_filters(&["status", "amount"]).unwrap(), &[
+    ///     Filter { field: String::from("status"), op: FilterOp::Eq, value: String::from("open") },
+    ///     Filter { field: String::from("amount"), op: FilterOp::Gte, value: String::from("100") },
+    /// ]);
+    /// assert!(q.parse_filters(&["status"]).is_err());
+    /// let q = QuerySearch { filter: Some(String::from("status:is:open")), ..Default::default() };
+    /// assert!(q.parse_filters(&["status"]).is_err());
+    /// assert!(QuerySearch::default().parse_filters(&["status"]).unwrap().is_empty());
+    /// ```
+    pub fn parse_filters(&self, allowed_fields: &[&str]) -> AppResult<Vec<Filter>> {
+        let mut filters = Vec::new();
+        let mut errors = None;
+        let expressions = self.filter.as_deref().unwrap_or("").split(',');
+        for expr in expressions.map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = expr.splitn(3, ':');
+            let (field, op, value) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(field), Some(op), Some(value)) if !field.is_empty() => (field, op, value),
+                _ => {
+                    errors = Some(add_invalid_argument(
+                        errors,
+                        "Invalid filter argument",
+                        "filter",
+                        ErrorCode::InvalidFilter.as_str(),
+                        format!("Invalid filter \"{expr}\", expected the form field:op:value"),
+                        json!({ "value": expr }),
+                    ));
+                    continue;
+                }
+            };
+            if !allowed_fields.contains(&field) {
+                errors = Some(add_invalid_argument(
+                    errors,
+                    "Invalid filter argument",
+                    "filter",
+                    ErrorCode::InvalidFilterField.as_str(),
+                    format!("Filtering by \"{field}\" is not allowed"),
+                    json!({ "value": field, "allowed": allowed_fields }),
+                ));
+                continue;
+            }
+            match op.parse::<FilterOp>() {
+                Ok(op) => filters.push(Filter {
+                    field: field.to_owned(),
+                    op,
+                    value: value.to_owned(),
+                }),
+                Err(_) => {
+                    let allowed: Vec<&str> = FilterOp::ALL.iter().map(FilterOp::as_str).collect();
+                    errors = Some(add_invalid_argument(
+                        errors,
+                        "Invalid filter argument",
+                        "filter",
+                        ErrorCode::InvalidFilterOp.as_str(),
+                        format!("Invalid filter operator \"{op}\""),
+                        json!({ "value": op, "allowed": allowed }),
+                    ));
+                }
+            }
+        }
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(filters),
+        }
+    }
+
+    /// Parse the filter argument (see [`QuerySearch::parse_filters()`])
+    /// into the struct `F`, with the values of the filters coerced to the
+    /// types of the fields, see [`FromQuerySearch`].
+    pub fn filters_as<F: FromQuerySearch>(&self) -> AppResult<F> {
+        let mut values = FilterValues::new(self.parse_filters(F::FIELDS)?);
+        let filters = F::from_filters(&mut values);
+        values.finish().map(|_| filters)
+    }
+
+    /// Build the [Elasticsearch](https://www.elastic.co/guide/en/elasticsearch/reference/current/query-dsl-bool-query.html)
+    /// search request with the same semantics of the URL arguments: a `bool`
+    /// query matching the search argument `q` in the search fields passed,
+    /// and the filters (see [`QuerySearch::parse_filters()`]) as `term`,
+    /// `range` and `wildcard` queries, followed by the sort (see
+    /// [`QuerySearch::parse_sort()`]), `from` and `size`.
+    ///
+    /// The filters and the sort are checked against the allowed fields,
+    /// the same as with the SQL queries.
+    ///
+    /// Only available when the `elastic` feature is activated.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearch;
+    /// use serde_json::json;
+    ///
+    /// let q = QuerySearch {
+    ///     q: Some(String::from("john")),
+    ///     filter: Some(String::from("status:eq:open,amount:gte:100")),
+    ///     sort: Some(String::from("-amount")),
+    ///     page_size: 10,
+    ///     ..Default::default()
+    /// };
+    /// let es = q.to_es_query(&["name", "email"], &["status", "amount"]).unwrap();
+    /// assert_eq!(es, json!({
+    ///     "query": { "bool": {
+    ///         "must": [{ "multi_match": { "query": "john", "fields": ["name", "email"] } }],
+    ///         "filter": [
+    ///             { "term": { "status": "open" } },
+    ///             { "range": { "amount": { "gte": "100" } } },
+    ///         ],
+    ///         "must_not": [],
+    ///     } },
+    ///     "sort": [{ "amount": { "order": "desc" } }],
+    ///     "from": 0,
+    ///     "size": 10,
+    /// }));
+    ///
+    /// // The `LIKE` wildcards are translated, and the rest matched literally
+    /// let q = QuerySearch { filter: Some(String::from(r"name:like:50\%*_off%")), ..Default::default() };
+    /// let es = q.to_es_query(&["name"], &["name"]).unwrap();
+    /// assert_eq!(es["query"]["bool"]["filter"][0], json!({ "wildcard": { "name": r"50%\*?off*" } }));
+    /// ```
+    #[cfg(feature = "elastic")]
+    pub fn to_es_query(&self, search_fields: &[&str], allowed_fields: &[&str]) -> AppResult<Value> {
+        let mut must = Vec::new();
+        if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            must.push(json!({ "multi_match": { "query": q, "fields": search_fields } }));
+        }
+        let (mut filter, mut must_not) = (Vec::new(), Vec::new());
+        for Filter { field, op, value } in self.parse_filters(allowed_fields)? {
+            match op {
+                FilterOp::Eq => filter.push(json!({ "term": { field: value } })),
+                FilterOp::Ne => must_not.push(json!({ "term": { field: value } })),
+                FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte => {
+                    filter.push(json!({ "range": { field: { op.as_str(): value } } }))
+                }
+                FilterOp::Like => {
+                    filter.push(json!({ "wildcard": { field: like_to_wildcard(&value) } }))
+                }
+            }
+        }
+        let sort: Vec<Value> = self
+            .sort
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .filter_map(SortField::parse)
+            .filter(|sort| allowed_fields.contains(&sort.field.as_str()))
+            .map(|sort| {
+                let mut order = json!({ "order": sort.direction.as_str() });
+                if let Some(nulls) = sort.nulls {
+                    order["missing"] = json!(match nulls {
+                        Nulls::First => "_first",
+                        Nulls::Last => "_last",
+                    });
+                }
+                json!({ sort.field: order })
+            })
+            .collect();
+        let mut query = json!({
+            "query": { "bool": { "must": must, "filter": filter, "must_not": must_not } },
+            "sort": sort,
+            "from": self.offset,
+            "size": self.page_size,
+        });
+        if self.include_total == Some(true) {
+            query["track_total_hits"] = json!(true);
+        }
+        Ok(query)
+    }
+
+    /// Build a SQL query starting with the `SELECT` statement passed
+    /// (without `WHERE` clause), followed by the conditions of the
+    /// filter argument (see [`QuerySearch::parse_filters()`]), the
+    /// `ORDER BY` clause of the sort argument (see
+    /// [`QuerySearch::sort_as_order_by_args()`]), and the `LIMIT` and
+    /// `OFFSET` of the page. All the values are passed as bind
+    /// parameters, never interpolated into the SQL.
+    ///
+    /// The filter values are bound as `text`, so the allowed fields
+    /// that are not text columns have to declare the type to cast
+    /// the values to, with the form `field::type`, e.g. `amount::numeric`.
+    ///
+    /// Only available when the `sqlx-postgres` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// // GET /orders?filter=status:eq:open,amount:gte:100&sort=-amount
+    /// let orders: Vec<Order> = query
+    ///     .to_query_builder("SELECT * FROM orders", &["status", "amount::numeric"], "id")?
+    ///     .build_query_as()
+    ///     .fetch_all(&mut *tx)
+    ///     .await?;
+    /// // SELECT * FROM orders WHERE status = $1 AND amount >= $2::numeric
+    /// //   ORDER BY amount DESC LIMIT $3 OFFSET $4
+    /// ```
+    #[cfg(feature = "sqlx-postgres")]
+    pub fn to_query_builder<'a>(
+        &self,
+        select: &str,
+        allowed_fields: &[&str],
+        default_sort: &str,
+    ) -> AppResult<QueryBuilder<'a, Postgres>> {
+        let columns: Vec<(&str, Option<&str>)> = allowed_fields
+            .iter()
+            .map(|f| match f.split_once("::") {
+                Some((name, cast)) => (name, Some(cast)),
+                None => (*f, None),
+            })
+            .collect();
+        let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+        let filters = self.parse_filters(&names)?;
+        let mut builder = QueryBuilder::new(select);
+        for (i, filter) in filters.into_iter().enumerate() {
+            let cast = columns
+                .iter()
+                .find(|(name, _)| *name == filter.field)
+                .and_then(|(_, cast)| *cast);
+            builder
+                .push(if i == 0 { " WHERE " } else { " AND " })
+                .push(format_args!("{} {} ", filter.field, filter.op.as_sql()))
+                .push_bind(filter.value);
+            if let Some(cast) = cast {
+                builder.push(format_args!("::{cast}"));
+            }
+        }
+        builder
+            .push(" ORDER BY ")
+            .push(self.sort_as_order_by_args(&names, default_sort))
+            .push(" LIMIT ")
+            .push_bind(self.page_size)
+            .push(" OFFSET ")
+            .push_bind(self.offset);
+        Ok(builder)
+    }
+}
+
+/// Percent-encode the value to be included in a query string, keeping
+/// only the unreserved characters of the
+/// [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986#section-2.3).
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
         }
     }
+    encoded
 }
 
+/// Split the text by whitespaces, except the ones between double quotes.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if let Some(start) = start.take() {
+                    words.push(&text[start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        words.push(&text[start..]);
+    }
+    words
+}
 
-/// Struct used to deserialize with `serde` query strings
-/// from a request URL with the `force` argument, that
-/// can be either true or false, or not be set at all.
-#[derive(Debug, Clone, Deserialize, Validate)]
-pub struct Force {
-    pub force: Option<bool>,
+/// Direction of a [`SortField`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    /// The name of the direction in lower case, e.g. `"desc"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+/// Position of the null values of a [`SortField`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+/// A field of the sort argument, parsed with [`QuerySearch::parse_sort()`],
+/// formatted as SQL with the form `field [DESC] [NULLS FIRST|LAST]`.
+///
+/// ```
+/// use actix_contrib_rest::query::{Direction, Nulls, SortField};
+///
+/// let sort = SortField::parse("-ended_at!nulls_last").unwrap();
+/// assert_eq!(sort, SortField {
+///     field: String::from("ended_at"),
+///     direction: Direction::Desc,
+///     nulls: Some(Nulls::Last),
+/// });
+/// assert_eq!(sort.to_string(), "ended_at DESC NULLS LAST");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SortField {
+    pub field: String,
+    pub direction: Direction,
+    pub nulls: Option<Nulls>,
+}
+
+impl SortField {
+    /// Parse an item of the sort argument, e.g. `-ended_at!nulls_last`.
+    /// `None` is returned if the item is empty or the suffix is unknown.
+    pub fn parse(item: &str) -> Option<SortField> {
+        let (field, nulls) = match item.split_once('!') {
+            Some((field, "nulls_first")) => (field, Some(Nulls::First)),
+            Some((field, "nulls_last")) => (field, Some(Nulls::Last)),
+            Some(_) => return None,
+            None => (item, None),
+        };
+        let (field, direction) = match field.strip_prefix('-') {
+            Some(field) => (field, Direction::Desc),
+            None => (field, Direction::Asc),
+        };
+        (!field.is_empty()).then(|| SortField {
+            field: field.to_owned(),
+            direction,
+            nulls,
+        })
+    }
+}
+
+impl fmt::Display for SortField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.field)?;
+        if self.direction == Direction::Desc {
+            f.write_str(" DESC")?;
+        }
+        match self.nulls {
+            Some(Nulls::First) => f.write_str(" NULLS FIRST"),
+            Some(Nulls::Last) => f.write_str(" NULLS LAST"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Join the sorting columns into an `ORDER BY` expression,
+/// or the default expression if there are no columns.
+fn order_by_args(sorting: Vec<SortField>, default: &str) -> String {
+    match sorting.len() {
+        0 => String::from(default),
+        _ => sorting.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+    }
+}
+
+/// Extract the query search from the query string of the request,
+/// with the page size limits of the [`QuerySearchConfig`] registered
+/// with `app_data`, if any.
+impl FromRequest for QuerySearch {
+    type Error = AppError;
+    type Future = Ready<AppResult<Self>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = req.app_data::<QuerySearchConfig>().copied().unwrap_or_default();
+        ready(config.parse(req.query_string()))
+    }
+}
+
+/// Configuration of the [`QuerySearch`] extractor, with the page
+/// size used when the `page_size` argument is not set (`50` by default),
+/// and the maximum page size allowed (no limit by default).
+///
+/// By default page sizes above the maximum are rejected with an
+/// `HTTP 400 Bad Request` error, with a `field_errors` entry
+/// for `page_size`, unless clamping is enabled, in which case
+/// the maximum is used instead. In the same way, if the
+/// sort fields allowed are set, the sort arguments with other
+/// columns are rejected with a `field_errors` entry for `sort`,
+/// instead of being silently ignored by [`QuerySearch::parse_sort()`].
+///
+/// The default sort and `include_total` values can be set as well,
+/// and as any `app_data`, the configuration can be registered per
+/// scope or resource, so each endpoint can have its own defaults.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::query::{QuerySearch, QuerySearchConfig};
+/// use actix_web::{web, App, HttpResponse, Responder};
+///
+/// async fn list_users(query: QuerySearch) -> impl Responder {
+///     // query.page_size is 20 if not set, and never above 100
+///     HttpResponse::Ok()
+/// }
+///
+/// async fn list_sales(query: QuerySearch) -> impl Responder {
+///     // query.sort is "-created_at" if not set
+///     HttpResponse::Ok()
+/// }
+///
+/// fn main() {
+///     let app = App::new()
+///         .app_data(
+///             QuerySearchConfig::default()
+///                 .default_page_size(20)
+///                 .max_page_size(100)
+///                 .sort_fields(&["name", "email", "created_at"])
+///         )
+///         .route("/users", web::get().to(list_users))
+///         .service(
+///             web::resource("/sales")
+///                 .app_data(
+///                     QuerySearchConfig::default()
+///                         .default_sort("-created_at")
+///                         .default_include_total(true)
+///                 )
+///                 .route(web::get().to(list_sales))
+///         );
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuerySearchConfig {
+    default_page_size: i64,
+    max_page_size: Option<i64>,
+    clamp: bool,
+    sort_fields: Option<&'static [&'static str]>,
+    max_offset: Option<i64>,
+    default_sort: Option<&'static str>,
+    default_include_total: Option<bool>,
+}
+
+impl Default for QuerySearchConfig {
+    fn default() -> Self {
+        QuerySearchConfig {
+            default_page_size: default_page_size(),
+            max_page_size: None,
+            clamp: false,
+            sort_fields: None,
+            max_offset: None,
+            default_sort: None,
+            default_include_total: None,
+        }
+    }
+}
+
+impl QuerySearchConfig {
+    /// Set the page size used when the `page_size` argument is not set.
+    pub fn default_page_size(mut self, page_size: i64) -> Self {
+        self.default_page_size = page_size;
+        self
+    }
+
+    /// Set the maximum page size allowed.
+    pub fn max_page_size(mut self, page_size: i64) -> Self {
+        self.max_page_size = Some(page_size);
+        self
+    }
+
+    /// Set whether the page sizes above the maximum are clamped
+    /// to the maximum instead of being rejected.
+    pub fn clamp(mut self, clamp: bool) -> Self {
+        self.clamp = clamp;
+        self
+    }
+
+    /// Set the fields allowed in the sort argument.
+    pub fn sort_fields(mut self, fields: &'static [&'static str]) -> Self {
+        self.sort_fields = Some(fields);
+        self
+    }
+
+    /// Set the sort argument used when it's not set, e.g. `-created_at`.
+    pub fn default_sort(mut self, sort: &'static str) -> Self {
+        self.default_sort = Some(sort);
+        self
+    }
+
+    /// Set the `include_total` argument used when it's not set.
+    pub fn default_include_total(mut self, include_total: bool) -> Self {
+        self.default_include_total = Some(include_total);
+        self
+    }
+
+    /// Set the maximum offset allowed, to protect the database from the
+    /// slow scans of deep pages (e.g. `OFFSET 5000000`) requested by
+    /// crawlers. Greater offsets are rejected with an `HTTP 400 Bad Request`
+    /// error, with a `field_errors` entry for `offset` suggesting the
+    /// cursor-based pagination, see [`CursorPage`](crate::page::CursorPage).
+    pub fn max_offset(mut self, offset: i64) -> Self {
+        self.max_offset = Some(offset);
+        self
+    }
+
+    /// Parse the query string passed into a [`QuerySearch`], applying
+    /// the defaults, the page size limits, the sort fields and the
+    /// maximum offset of the configuration.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::QuerySearchConfig;
+    ///
+    /// let config = QuerySearchConfig::default().default_page_size(20).max_page_size(100);
+    /// assert_eq!(config.parse("q=john").unwrap().page_size, 20);
+    /// assert_eq!(config.parse("page_size=100").unwrap().page_size, 100);
+    /// assert!(config.parse("page_size=1000000").is_err());
+    /// assert_eq!(config.clamp(true).parse("page_size=1000000").unwrap().page_size, 100);
+    /// assert_eq!(config.parse("page=3").unwrap().offset, 40);
+    /// assert!(config.parse("page=3&offset=40").is_err());
+    /// assert!(config.max_offset(1000).parse("page=100").is_err());
+    ///
+    /// let config = QuerySearchConfig::default().default_sort("-created_at").default_include_total(true);
+    /// let query = config.parse("q=john").unwrap();
+    /// assert_eq!((query.sort.as_deref(), query.include_total), (Some("-created_at"), Some(true)));
+    /// assert_eq!(config.parse("sort=name").unwrap().sort.as_deref(), Some("name"));
+    ///
+    /// let config = QuerySearchConfig::default().sort_fields(&["name", "email"]);
+    /// assert!(config.parse("sort=name,-email").is_ok());
+    /// assert!(config.parse("sort=name,-password").is_err());
+    /// ```
+    pub fn parse(&self, query_string: &str) -> AppResult<QuerySearch> {
+        let mut query = web::Query::<QuerySearch>::from_query(query_string)
+            .map_err(invalid_query_string)?
+            .into_inner();
+        let args = web::Query::<Vec<(String, String)>>::from_query(query_string)
+            .map(web::Query::into_inner)
+            .unwrap_or_default();
+        let is_set = |arg: &str| args.iter().any(|(name, _)| name == arg);
+        if !is_set("page_size") {
+            query.page_size = self.default_page_size;
+        }
+        if query.sort.is_none() {
+            query.sort = self.default_sort.map(String::from);
+        }
+        if query.include_total.is_none() {
+            query.include_total = self.default_include_total;
+        }
+        if is_set("page") && is_set("offset") {
+            return Err(invalid_argument(
+                "Invalid query string",
+                "page",
+                ErrorCode::PageAndOffset.as_str(),
+                "The page and the offset cannot be set at the same time",
+                json!({ "value": query.page }),
+            ));
+        }
+        self.check(query)
+    }
+
+    /// Clamp or reject the page size, check the sort fields, validate the
+    /// query and apply the page number, with the settings of the config.
+    fn check(&self, mut query: QuerySearch) -> AppResult<QuerySearch> {
+        if let Some(max) = self.max_page_size.filter(|max| query.page_size > *max) {
+            if !self.clamp {
+                return Err(invalid_argument(
+                    "Invalid query string",
+                    "page_size",
+                    ErrorCode::Range.as_str(),
+                    format!("The page size cannot be greater than {max}"),
+                    json!({ "max": max, "value": query.page_size }),
+                ));
+            }
+            query.page_size = max;
+        }
+        if let Some(fields) = self.sort_fields {
+            query.check_sort(fields)?;
+        }
+        query
+            .validate()
+            .map_err(|e| AppError::Custom(StatusCode::BAD_REQUEST, ValidationErrorPayload::from(&e)))?;
+        query.apply_page()?;
+        if let Some(max) = self.max_offset.filter(|max| query.offset > *max) {
+            return Err(invalid_argument(
+                "Invalid query string",
+                "offset",
+                ErrorCode::Range.as_str(),
+                format!("The offset cannot be greater than {max}, use cursor-based pagination instead"),
+                json!({ "max": max, "value": query.offset }),
+            ));
+        }
+        Ok(query)
+    }
+}
+
+/// Operator of a [`Filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterOp {
+    /// `eq`: equal to.
+    Eq,
+    /// `ne`: not equal to.
+    Ne,
+    /// `gt`: greater than.
+    Gt,
+    /// `gte`: greater than or equal to.
+    Gte,
+    /// `lt`: less than.
+    Lt,
+    /// `lte`: less than or equal to.
+    Lte,
+    /// `like`: matches the pattern, with `%` and `_` as wildcards.
+    Like,
+}
+
+impl FilterOp {
+    /// All the operators supported.
+    pub const ALL: &'static [FilterOp] = &[
+        Self::Eq,
+        Self::Ne,
+        Self::Gt,
+        Self::Gte,
+        Self::Lt,
+        Self::Lte,
+        Self::Like,
+    ];
+
+    /// The name of the operator in the filter argument, e.g. `"gte"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::Gt => "gt",
+            Self::Gte => "gte",
+            Self::Lt => "lt",
+            Self::Lte => "lte",
+            Self::Like => "like",
+        }
+    }
+
+    /// The SQL operator, e.g. `">="`.
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Ne => "<>",
+            Self::Gt => ">",
+            Self::Gte => ">=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+            Self::Like => "LIKE",
+        }
+    }
+}
+
+impl fmt::Display for FilterOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FilterOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|op| op.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown filter operator \"{s}\""))
+    }
+}
+
+/// A filter parsed from the filter argument of the query,
+/// see [`QuerySearch::parse_filters()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub field: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+/// The search argument parsed into the qualifiers and the free text,
+/// see [`QuerySearch::parse_qualifiers()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQualifiers {
+    /// The values of each qualifier, in the order of the search.
+    pub qualifiers: HashMap<String, Vec<String>>,
+    /// The rest of the search, without the qualifiers.
+    pub free_text: String,
+}
+
+/// Trait of the structs populated with the values of the filter argument,
+/// parsed with [`QuerySearch::filters_as()`], e.g. a struct with the
+/// filters supported by an endpoint, with the values coerced to the types
+/// of the fields (with [`FromStr`]), instead of dealing with strings.
+///
+/// The values that cannot be coerced are rejected with an
+/// `HTTP 400 Bad Request` error, with a `field_errors` entry for
+/// each field, the same as the filters not read by the struct,
+/// e.g. with an operator not supported for the field.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::query::{FilterOp, FilterValues, FromQuerySearch, QuerySearch};
+///
+/// #[derive(Debug)]
+/// struct SalesFilters {
+///     customer_id: Option<i64>,
+///     status: Option<String>,
+///     min_total: Option<f64>,
+/// }
+///
+/// impl FromQuerySearch for SalesFilters {
+///     const FIELDS: &'static [&'static str] = &["customer_id", "status", "total"];
+///
+///     fn from_filters(filters: &mut FilterValues) -> Self {
+///         SalesFilters {
+///             customer_id: filters.value("customer_id", FilterOp::Eq),
+///             status: filters.value("status", FilterOp::Eq),
+///             min_total: filters.value("total", FilterOp::Gte),
+///         }
+///     }
+/// }
+///
+/// let q = QuerySearch { filter: Some(String::from("customer_id:eq:12,total:gte:99.5")), ..Default::default() };
+/// let filters: SalesFilters = q.filters_as().unwrap();
+/// assert_eq!((filters.customer_id, filters.status, filters.min_total), (Some(12), None, Some(99.5)));
+///
+/// let q = QuerySearch { filter: Some(String::from("customer_id:eq:john")), ..Default::default() };
+/// assert!(q.filters_as::<SalesFilters>().is_err());
+/// let q = QuerySearch { filter: Some(String::from("total:lt:10")), ..Default::default() };
+/// assert!(q.filters_as::<SalesFilters>().is_err());
+/// ```
+pub trait FromQuerySearch: Sized {
+    /// The fields that can be filtered.
+    const FIELDS: &'static [&'static str];
+
+    /// Build the struct with the values of the filters.
+    fn from_filters(filters: &mut FilterValues) -> Self;
+}
+
+/// The filters parsed from the filter argument, with the values
+/// read by [`FromQuerySearch::from_filters()`], collecting the errors.
+#[derive(Debug)]
+pub struct FilterValues {
+    filters: Vec<(Filter, bool)>,
+    errors: Option<AppError>,
+}
+
+impl FilterValues {
+    fn new(filters: Vec<Filter>) -> Self {
+        FilterValues {
+            filters: filters.into_iter().map(|filter| (filter, false)).collect(),
+            errors: None,
+        }
+    }
+
+    /// The value of the filter with the field and the operator passed,
+    /// coerced to `T`, if set. If the value cannot be coerced, the error
+    /// is collected and `None` is returned.
+    pub fn value<T>(&mut self, field: &str, op: FilterOp) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value_with(field, op, |value| value.parse::<T>().map_err(|e| e.to_string()))
+    }
+
+    /// Same as [`FilterValues::value()`] but for boolean values,
+    /// see [`parse_bool()`].
+    pub fn bool(&mut self, field: &str, op: FilterOp) -> Option<bool> {
+        self.value_with(field, op, |value| {
+            parse_bool(value).ok_or_else(|| format!("Invalid boolean \"{value}\""))
+        })
+    }
+
+    fn value_with<T>(
+        &mut self,
+        field: &str,
+        op: FilterOp,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        let (filter, read) = self
+            .filters
+            .iter_mut()
+            .find(|(filter, _)| filter.field == field && filter.op == op)?;
+        *read = true;
+        match parse(&filter.value) {
+            Ok(value) => Some(value),
+            Err(message) => {
+                let value = filter.value.clone();
+                self.error(field, ErrorCode::InvalidValue.as_str(), message, value);
+                None
+            }
+        }
+    }
+
+    /// Add an error for the field passed.
+    fn error(&mut self, field: &str, code: &'static str, message: String, value: String) {
+        self.errors = Some(add_invalid_argument(
+            self.errors.take(),
+            "Invalid filter argument",
+            field.to_owned(),
+            code,
+            message,
+            json!({ "value": value }),
+        ));
+    }
+
+    /// Check all the filters were read without errors.
+    fn finish(mut self) -> AppResult<()> {
+        let unread: Vec<Filter> = self
+            .filters
+            .iter()
+            .filter(|(_, read)| !read)
+            .map(|(filter, _)| filter.clone())
+            .collect();
+        for filter in unread {
+            let message = format!("Operator \"{}\" not supported for \"{}\"", filter.op, filter.field);
+            self.error(&filter.field, ErrorCode::InvalidFilterOp.as_str(), message, filter.value);
+        }
+        match self.errors {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Expression with the filters combined with logical operators,
+/// e.g. parsed with [`parse_odata_filter()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpr {
+    /// A single filter.
+    Filter(Filter),
+    /// All the expressions have to match.
+    And(Vec<FilterExpr>),
+    /// Any of the expressions has to match.
+    Or(Vec<FilterExpr>),
+}
+
+/// Parse a filter expression with a safe subset of the
+/// [OData](https://docs.oasis-open.org/odata/odata/v4.01/odata-v4.01-part2-url-conventions.html#sec_SystemQueryOptionfilter)
+/// `$filter` syntax:
+/// - The `eq`, `ne`, `gt`, `ge` (greater than or equal to), `lt` and `le`
+///   (less than or equal to) comparison operators, mapped to the [`FilterOp`]
+///   operators with the same meaning, e.g. `ge` to [`FilterOp::Gte`].
+/// - The `contains(field, 'value')` function, mapped to [`FilterOp::Like`].
+/// - The `and` and `or` logical operators, and parentheses, nested up to
+///   32 levels deep.
+///
+/// Strings are enclosed in single quotes, escaped by doubling them, e.g. `'O''Brien'`.
+///
+/// Expressions with other constructs, nested too deep, or filtering by fields
+/// not included in the allowed fields, are rejected with an `HTTP 400 Bad Request`
+/// error, with a `field_errors` entry for `$filter`.
+///
+/// Only available when the `odata` feature is activated.
+///
+/// ```
+/// use actix_contrib_rest::query::{parse_odata_filter, Filter, FilterExpr, FilterOp};
+///
+/// let expr = parse_odata_filter(
+///     "status eq 'open' and (amount gt 100 or contains(name, 'john'))",
+///     &["status", "amount", "name"],
+/// ).unwrap();
+/// let filter = |field: &str, op, value: &str| {
+///     FilterExpr::Filter(Filter { field: field.to_owned(), op, value: value.to_owned() })
+/// };
+/// assert_eq!(expr, FilterExpr::And(vec![
+///     filter("status", FilterOp::Eq, "open"),
+///     FilterExpr::Or(vec![
+///         filter("amount", FilterOp::Gt, "100"),
+///         filter("name", FilterOp::Like, "%john%"),
+///     ]),
+/// ]));
+/// assert!(parse_odata_filter("password eq 'x'", &["status"]).is_err());
+/// assert!(parse_odata_filter("status eq 'open", &["status"]).is_err());
+/// let nested = format!("{}status eq 'open'{}", "(".repeat(100), ")".repeat(100));
+/// assert!(parse_odata_filter(&nested, &["status"]).is_err());
+/// ```
+#[cfg(feature = "odata")]
+pub fn parse_odata_filter(expr: &str, allowed_fields: &[&str]) -> AppResult<FilterExpr> {
+    let invalid = |message: String| {
+        invalid_argument(
+            "Invalid filter argument",
+            "$filter",
+            ErrorCode::InvalidFilter.as_str(),
+            message,
+            json!({ "value": expr }),
+        )
+    };
+    let mut parser = ODataParser {
+        tokens: ODataToken::tokenize(expr).map_err(invalid)?,
+        pos: 0,
+        depth: 0,
+        allowed_fields,
+    };
+    let filter = parser.or_expr().map_err(invalid)?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(invalid(format!("Unexpected {token:?}"))),
+        None => Ok(filter),
+    }
+}
+
+#[cfg(feature = "odata")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ODataToken {
+    Word(String),
+    Str(String),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[cfg(feature = "odata")]
+impl ODataToken {
+    fn tokenize(expr: &str) -> Result<Vec<ODataToken>, String> {
+        let mut tokens = Vec::new();
+        let mut chars = expr.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '(' => tokens.push(Self::LParen),
+                ')' => tokens.push(Self::RParen),
+                ',' => tokens.push(Self::Comma),
+                '\'' => {
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\'') if chars.peek() == Some(&'\'') => {
+                                chars.next();
+                                value.push('\'');
+                            }
+                            Some('\'') => break,
+                            Some(c) => value.push(c),
+                            None => return Err("Unterminated string".to_owned()),
+                        }
+                    }
+                    tokens.push(Self::Str(value));
+                }
+                c if c.is_whitespace() => {}
+                c if c.is_alphanumeric() || "_-.:".contains(c) => {
+                    let mut word = String::from(c);
+                    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || "_-.:".contains(*c)) {
+                        word.push(c);
+                    }
+                    tokens.push(Self::Word(word));
+                }
+                c => return Err(format!("Unexpected character '{c}'")),
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+/// Recursive descent parser of the OData `$filter` subset,
+/// where `and` has higher precedence than `or`.
+#[cfg(feature = "odata")]
+struct ODataParser<'a> {
+    tokens: Vec<ODataToken>,
+    pos: usize,
+    /// Nesting level of the parentheses being parsed
+    depth: usize,
+    allowed_fields: &'a [&'a str],
+}
+
+#[cfg(feature = "odata")]
+impl ODataParser<'_> {
+    /// Max nesting level of the parentheses, so deeply
+    /// nested expressions cannot overflow the stack.
+    const MAX_DEPTH: usize = 32;
+
+    fn next(&mut self) -> Option<ODataToken> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn next_if_word(&mut self, word: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(ODataToken::Word(w)) if w == word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: ODataToken) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            _ => Err(format!("Expected {token:?}")),
+        }
+    }
+
+    fn field(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(ODataToken::Word(field)) if self.allowed_fields.contains(&field.as_str()) => Ok(field),
+            Some(ODataToken::Word(field)) => Err(format!("Filtering by \"{field}\" is not allowed")),
+            _ => Err("Expected a field".to_owned()),
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<FilterExpr, String> {
+        let mut exprs = vec![self.and_expr()?];
+        while self.next_if_word("or") {
+            exprs.push(self.and_expr()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => FilterExpr::Or(exprs),
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<FilterExpr, String> {
+        let mut exprs = vec![self.primary()?];
+        while self.next_if_word("and") {
+            exprs.push(self.primary()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.remove(0),
+            _ => FilterExpr::And(exprs),
+        })
+    }
+
+    fn primary(&mut self) -> Result<FilterExpr, String> {
+        if self.tokens.get(self.pos) == Some(&ODataToken::LParen) {
+            if self.depth == Self::MAX_DEPTH {
+                return Err(format!("Expressions cannot be nested more than {} levels", Self::MAX_DEPTH));
+            }
+            self.pos += 1;
+            self.depth += 1;
+            let expr = self.or_expr()?;
+            self.depth -= 1;
+            self.expect(ODataToken::RParen)?;
+            return Ok(expr);
+        }
+        if self.next_if_word("contains") {
+            self.expect(ODataToken::LParen)?;
+            let field = self.field()?;
+            self.expect(ODataToken::Comma)?;
+            let value = match self.next() {
+                Some(ODataToken::Str(value)) => value,
+                _ => return Err("Expected a string in contains()".to_owned()),
+            };
+            self.expect(ODataToken::RParen)?;
+            let value = format!("%{}%", escape_like(&value));
+            return Ok(FilterExpr::Filter(Filter { field, op: FilterOp::Like, value }));
+        }
+        let field = self.field()?;
+        let op = match self.next() {
+            Some(ODataToken::Word(op)) => match op.as_str() {
+                "eq" => FilterOp::Eq,
+                "ne" => FilterOp::Ne,
+                "gt" => FilterOp::Gt,
+                "ge" => FilterOp::Gte,
+                "lt" => FilterOp::Lt,
+                "le" => FilterOp::Lte,
+                _ => return Err(format!("Unknown operator \"{op}\"")),
+            },
+            _ => return Err("Expected an operator".to_owned()),
+        };
+        let value = match self.next() {
+            Some(ODataToken::Str(value)) | Some(ODataToken::Word(value)) => value,
+            _ => return Err("Expected a value".to_owned()),
+        };
+        Ok(FilterExpr::Filter(Filter { field, op, value }))
+    }
+}
+
+/// Translate a `LIKE` pattern into an Elasticsearch `wildcard` pattern:
+/// `%` into `*` and `_` into `?`, the characters escaped with a backslash
+/// (see [`escape_like()`]) are matched literally, and the `*`, `?` and
+/// `\` literal characters are escaped.
+#[cfg(feature = "elastic")]
+fn like_to_wildcard(pattern: &str) -> String {
+    let mut wildcard = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '%' => {
+                wildcard.push('*');
+                continue;
+            }
+            '_' => {
+                wildcard.push('?');
+                continue;
+            }
+            // A trailing backslash is matched literally
+            '\\' => chars.next().unwrap_or('\\'),
+            c => c,
+        };
+        if matches!(literal, '*' | '?' | '\\') {
+            wildcard.push('\\');
+        }
+        wildcard.push(literal);
+    }
+    wildcard
+}
+
+/// Escape the `LIKE` wildcards (`%` and `_`) and the backslashes
+/// of the value, so it's matched literally.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Query arguments with the [JSON:API](https://jsonapi.org/format/#fetching)
+/// style, with the bracketed `page[size]`, `page[number]` (1-indexed),
+/// `filter[field]` and `filter[field][op]` arguments, and `sort`, e.g.
+/// `/api/articles?page[number]=2&page[size]=10&filter[status]=open&sort=-created_at`.
+///
+/// It can be used as an extractor, and converted with
+/// [`JsonApiQuery::into_query_search()`] into a [`QuerySearch`] and
+/// filters, so the same handlers can serve JSON:API clients.
+///
+/// ```
+/// use actix_contrib_rest::query::{FilterOp, JsonApiQuery, QuerySearchConfig};
+///
+/// let config = QuerySearchConfig::default().max_page_size(100);
+/// let query = JsonApiQuery::from_query(
+///     "page[number]=3&page[size]=10&filter[status]=open&filter[amount][gte]=100&sort=-amount"
+/// ).unwrap();
+/// let (query, filters) = query.into_query_search(&["status", "amount"], &config).unwrap();
+/// assert_eq!((query.offset, query.page_size), (20, 10));
+/// assert_eq!(query.sort.as_deref(), Some("-amount"));
+/// assert_eq!(filters[1].op, FilterOp::Gte);
+/// assert!(JsonApiQuery::from_query("page[size]=ten").is_err());
+/// let query = JsonApiQuery::from_query("page[number]=9223372036854775807&page[size]=50").unwrap();
+/// assert!(query.into_query_search(&[], &config).is_err());
+/// let query = JsonApiQuery::from_query("page[size]=1000").unwrap();
+/// assert!(query.into_query_search(&[], &config).is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonApiQuery {
+    pub page_size: Option<i64>,
+    pub page_number: Option<i64>,
+    pub sort: Option<String>,
+    pub filters: Vec<Filter>,
+}
+
+impl JsonApiQuery {
+    /// Parse the query string passed, returning an `HTTP 400 Bad Request`
+    /// error if it's malformed, or with a `field_errors` entry for each
+    /// invalid argument.
+    pub fn from_query(query_string: &str) -> AppResult<Self> {
+        let args = web::Query::<Vec<(String, String)>>::from_query(query_string)
+            .map(web::Query::into_inner)
+            .map_err(invalid_query_string)?;
+        let mut query = JsonApiQuery::default();
+        let mut errors = None;
+        for (name, value) in args {
+            let number = |min: i64| value.parse::<i64>().ok().filter(|n| *n >= min);
+            let result = match name.as_str() {
+                "page[size]" => number(1).map(|n| query.page_size = Some(n)),
+                "page[number]" => number(1).map(|n| query.page_number = Some(n)),
+                "sort" => {
+                    query.sort = Some(value.clone());
+                    Some(())
+                }
+                _ => match name.strip_prefix("filter[").and_then(|f| f.strip_suffix(']')) {
+                    Some(filter) => {
+                        let (field, op) = match filter.split_once("][") {
+                            Some((field, op)) => (field, op.parse::<FilterOp>().ok()),
+                            None => (filter, Some(FilterOp::Eq)),
+                        };
+                        op.map(|op| {
+                            query.filters.push(Filter {
+                                field: field.to_owned(),
+                                op,
+                                value: value.clone(),
+                            })
+                        })
+                    }
+                    // Other arguments like `include` are ignored
+                    None => Some(()),
+                },
+            };
+            if result.is_none() {
+                errors = Some(add_invalid_argument(
+                    errors,
+                    "Invalid query string",
+                    name.clone(),
+                    ErrorCode::InvalidArgument.as_str(),
+                    format!("Invalid value \"{value}\" for argument \"{name}\""),
+                    json!({ "value": value }),
+                ));
+            }
+        }
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(query),
+        }
+    }
+
+    /// Convert the arguments into a [`QuerySearch`] and the filters,
+    /// rejecting the filters on fields not included in the allowed fields
+    /// with an `HTTP 400 Bad Request` error, with a `field_errors` entry
+    /// for `filter`. The defaults and the limits of the page size, the sort
+    /// fields and the offset of the config passed are applied the same
+    /// way the [`QuerySearch`] extractor does.
+    pub fn into_query_search(
+        self,
+        allowed_fields: &[&str],
+        config: &QuerySearchConfig,
+    ) -> AppResult<(QuerySearch, Vec<Filter>)> {
+        let rejected: Vec<&str> = self
+            .filters
+            .iter()
+            .map(|f| f.field.as_str())
+            .filter(|f| !allowed_fields.contains(f))
+            .collect();
+        if !rejected.is_empty() {
+            return Err(invalid_argument(
+                "Invalid filter argument",
+                "filter",
+                ErrorCode::InvalidFilterField.as_str(),
+                format!("Filtering by \"{}\" is not allowed", rejected.join(", ")),
+                json!({ "value": rejected, "allowed": allowed_fields }),
+            ));
+        }
+        let query = QuerySearch {
+            sort: self.sort.or_else(|| config.default_sort.map(String::from)),
+            page: self.page_number,
+            page_size: self.page_size.unwrap_or(config.default_page_size),
+            include_total: config.default_include_total,
+            ..Default::default()
+        };
+        Ok((config.check(query)?, self.filters))
+    }
+}
+
+impl FromRequest for JsonApiQuery {
+    type Error = AppError;
+    type Future = Ready<AppResult<Self>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::from_query(req.query_string()))
+    }
+}
+
+
+/// Collect the values of the multi-valued argument with the name passed
+/// from the query string, either repeated (`?status=open&status=pending`),
+/// comma separated (`?status=open,pending`) or both, parsed into `T`.
+/// The empty values are skipped.
+///
+/// The values that cannot be parsed are rejected with an
+/// `HTTP 400 Bad Request` error, with a `field_errors`
+/// entry for the argument.
+///
+/// ```
+/// use actix_contrib_rest::query::{any_condition, multi_values};
+///
+/// let ids: Vec<i64> = multi_values("ids=1,2&ids=3&q=john", "ids").unwrap();
+/// assert_eq!(ids, &[1, 2, 3]);
+/// assert_eq!(any_condition("id", 1), "id = ANY($1)");
+/// assert!(multi_values::<i64>("ids=1,two", "ids").is_err());
+/// ```
+pub fn multi_values<T>(query_string: &str, name: &str) -> AppResult<Vec<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let args = web::Query::<Vec<(String, String)>>::from_query(query_string)
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    args.iter()
+        .filter(|(arg, _)| arg == name)
+        .flat_map(|(_, values)| values.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| coerce(name, value))
+        .collect()
+}
+
+/// Parse the boolean value passed, accepting `true`, `1`, `yes` and `on`
+/// as true, and `false`, `0`, `no` and `off` as false, case-insensitive.
+///
+/// ```
+/// use actix_contrib_rest::query::parse_bool;
+///
+/// assert_eq!(parse_bool("Yes"), Some(true));
+/// assert_eq!(parse_bool("0"), Some(false));
+/// assert_eq!(parse_bool("maybe"), None);
+/// ```
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Coerce the value of the query argument or filter passed into `T`, e.g.
+/// integers, UUIDs or enums, with its [`FromStr`] implementation. If the
+/// value cannot be coerced, an `HTTP 400 Bad Request` error is returned,
+/// with a `field_errors` entry for the field passed.
+///
+/// ```
+/// use actix_contrib_rest::query::{coerce, coerce_bool};
+///
+/// let id: i64 = coerce("customer_id", "42").unwrap();
+/// assert_eq!(id, 42);
+/// assert!(coerce::<i64>("customer_id", "john").is_err());
+/// assert!(coerce_bool("active", "on").unwrap());
+/// assert!(coerce_bool("active", "si").is_err());
+/// ```
+pub fn coerce<T>(field: &str, value: &str) -> AppResult<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|e| invalid_value(field, e.to_string(), value))
+}
+
+/// Same as [`coerce()`] but for boolean values, see [`parse_bool()`].
+pub fn coerce_bool(field: &str, value: &str) -> AppResult<bool> {
+    parse_bool(value).ok_or_else(|| invalid_value(field, format!("Invalid boolean \"{value}\""), value))
+}
+
+/// Error with a `field_errors` entry for the invalid value of the field.
+fn invalid_value(field: &str, message: String, value: &str) -> AppError {
+    invalid_argument(
+        "Invalid query string",
+        field.to_owned(),
+        ErrorCode::InvalidValue.as_str(),
+        message,
+        json!({ "value": value }),
+    )
+}
+
+/// Error of an invalid argument of the query string, with the error
+/// passed and a `field_errors` entry for the field.
+fn invalid_argument(
+    error: &str,
+    field: impl Into<String>,
+    code: &'static str,
+    message: impl Into<Cow<'static, str>>,
+    params: Value,
+) -> AppError {
+    let payload = ValidationErrorPayload::with_code(ErrorCode::ValidationError.to_string(), error.to_owned())
+        .field(field, code, message, params);
+    AppError::Custom(StatusCode::BAD_REQUEST, payload)
+}
+
+/// Add a `field_errors` entry to the invalid argument error passed,
+/// or build it with [`invalid_argument()`] if there is none yet.
+fn add_invalid_argument(
+    errors: Option<AppError>,
+    error: &str,
+    field: impl Into<String>,
+    code: &'static str,
+    message: impl Into<Cow<'static, str>>,
+    params: Value,
+) -> AppError {
+    match errors {
+        Some(AppError::Custom(status, payload)) => {
+            AppError::Custom(status, payload.field(field, code, message, params))
+        }
+        _ => invalid_argument(error, field, code, message, params),
+    }
+}
+
+/// Error of a query string that cannot be parsed.
+fn invalid_query_string(error: impl fmt::Display) -> AppError {
+    let payload = ValidationErrorPayload::with_code(
+        ErrorCode::ValidationError.to_string(),
+        format!("Invalid query string: {error}"),
+    );
+    AppError::Custom(StatusCode::BAD_REQUEST, payload)
+}
+
+/// Build the SQL condition to match the column passed with any of the
+/// values of the array passed as the bind parameter with the number
+/// passed, e.g. `status = ANY($1)`, to filter by the values of a
+/// multi-valued argument, see [`multi_values()`].
+pub fn any_condition(column: &str, param: usize) -> String {
+    format!("{column} = ANY(${param})")
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with the `force` argument, that
+/// can be either true or false, or not be set at all.
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct Force {
+    pub force: Option<bool>,
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with the `fields` argument, a comma
+/// separated list of the fields to include in the response
+/// (sparse fieldsets), e.g. `/api/users?fields=id,name`.
+///
+/// See [`Page::project()`](crate::page::Page::project).
+#[derive(Debug, Clone, Default, Deserialize, Validate, PartialEq, Eq)]
+pub struct Fields {
+    pub fields: Option<String>,
+}
+
+impl Fields {
+    /// Parse the `fields` argument into a vector of field names,
+    /// empty if the argument is not set.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::Fields;
+    /// let f = Fields { fields: Some(String::from("id, name,,email")) };
+    /// assert_eq!(f.parse(), &["id", "name", "email"]);
+    /// assert!(Fields { fields: None }.parse().is_empty());
+    /// ```
+    pub fn parse(&self) -> Vec<String> {
+        self.fields
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Same as [`Fields::parse()`], but the fields not included in the
+    /// allowed fields are rejected with an `HTTP 400 Bad Request` error,
+    /// with a `field_errors` entry for `fields` listing the rejected
+    /// fields and the allowed ones.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::Fields;
+    /// let f = Fields { fields: Some(String::from("id,name")) };
+    /// assert_eq!(f.parse_allowed(&["id", "name", "email"]).unwrap(), &["id", "name"]);
+    /// assert!(f.parse_allowed(&["id"]).is_err());
+    /// ```
+    pub fn parse_allowed(&self, allowed_fields: &[&str]) -> AppResult<Vec<String>> {
+        let fields = self.parse();
+        let rejected: Vec<&str> = fields
+            .iter()
+            .map(String::as_str)
+            .filter(|f| !allowed_fields.contains(f))
+            .collect();
+        if rejected.is_empty() {
+            return Ok(fields);
+        }
+        Err(invalid_argument(
+            "Invalid fields argument",
+            "fields",
+            ErrorCode::InvalidFields.as_str(),
+            format!("The fields \"{}\" are not allowed", rejected.join(", ")),
+            json!({ "value": rejected, "allowed": allowed_fields }),
+        ))
+    }
+
+    /// Build the list of columns of a SQL `SELECT` statement with the
+    /// fields requested, checked against the allowed fields (see
+    /// [`Fields::parse_allowed()`]), or the default list if the `fields`
+    /// argument is not set, e.g. `id, name` for `?fields=id,name`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::Fields;
+    /// let allowed = ["id", "name", "created_at"];
+    /// let f = Fields { fields: Some(String::from("id,created_at")) };
+    /// assert_eq!(f.select_columns(&allowed, "*").unwrap(), "id, created_at");
+    /// assert_eq!(Fields::default().select_columns(&allowed, "*").unwrap(), "*");
+    /// ```
+    pub fn select_columns(&self, allowed_fields: &[&str], default: &str) -> AppResult<String> {
+        let fields = self.parse_allowed(allowed_fields)?;
+        match fields.len() {
+            0 => Ok(String::from(default)),
+            _ => Ok(fields.join(", ")),
+        }
+    }
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with the `include` argument, a comma
+/// separated list of the relations to expand in the response,
+/// with nested relations separated by dots, e.g.
+/// `/api/posts?include=author,comments.author`.
+#[derive(Debug, Clone, Default, Deserialize, Validate, PartialEq, Eq)]
+pub struct Include {
+    pub include: Option<String>,
+}
+
+impl Include {
+    /// Parse the `include` argument into the tree of relations, checked
+    /// against the allowed relations, with the full path of the nested
+    /// ones, e.g. `comments.author`. The relations not allowed are rejected
+    /// with an `HTTP 400 Bad Request` error, with a `field_errors` entry
+    /// for `include` listing the rejected relations and the allowed ones.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::Include;
+    ///
+    /// let allowed = ["author", "comments", "comments.author"];
+    /// let include = Include { include: Some(String::from("author,comments.author")) };
+    /// let tree = include.parse_allowed(&allowed).unwrap();
+    /// assert!(tree.contains("author"));
+    /// assert!(tree.get("comments").unwrap().contains("author"));
+    /// assert!(!tree.contains("tags"));
+    /// let include = Include { include: Some(String::from("comments.author.posts")) };
+    /// assert!(include.parse_allowed(&allowed).is_err());
+    /// ```
+    pub fn parse_allowed(&self, allowed: &[&str]) -> AppResult<IncludeTree> {
+        let paths: Vec<&str> = self
+            .include
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .collect();
+        let rejected: Vec<&str> = paths.iter().copied().filter(|path| !allowed.contains(path)).collect();
+        if !rejected.is_empty() {
+            return Err(invalid_argument(
+                "Invalid include argument",
+                "include",
+                ErrorCode::InvalidInclude.as_str(),
+                format!("The relations \"{}\" cannot be included", rejected.join(", ")),
+                json!({ "value": rejected, "allowed": allowed }),
+            ));
+        }
+        let mut tree = IncludeTree::default();
+        for path in paths {
+            path.split('.').fold(&mut tree, |node, name| node.0.entry(name.to_owned()).or_default());
+        }
+        Ok(tree)
+    }
+}
+
+/// Tree of the relations to include in the response,
+/// see [`Include::parse_allowed()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IncludeTree(pub BTreeMap<String, IncludeTree>);
+
+impl IncludeTree {
+    /// Whether the relation is included, so the handler has
+    /// to run the join or the extra query to fetch it.
+    pub fn contains(&self, relation: &str) -> bool {
+        self.0.contains_key(relation)
+    }
+
+    /// The nested relations to include of the relation passed, if included.
+    pub fn get(&self, relation: &str) -> Option<&IncludeTree> {
+        self.0.get(relation)
+    }
+
+    /// Whether no relation is included.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with a range of dates, with the `from`
+/// and `to` arguments, both optional and inclusive, e.g.
+/// `/api/sales?from=2024-01-01T00:00:00Z&to=2024-01-31T23:59:59Z`.
+///
+/// The type of the dates is generic, e.g. [`UtcDateRange`] with
+/// the `chrono` feature. The validation fails if `from` is after `to`.
+///
+/// ```
+/// use actix_contrib_rest::query::DateRange;
+/// use validator::Validate;
+///
+/// let range = DateRange { from: Some("2024-01-01"), to: Some("2024-01-31") };
+/// assert!(range.validate().is_ok());
+/// assert_eq!(range.to_sql("created_at", 1).unwrap(), "created_at BETWEEN $1 AND $2");
+/// assert_eq!(range.values(), &[&"2024-01-01", &"2024-01-31"]);
+/// assert!(DateRange { from: Some("2024-02-01"), to: Some("2024-01-31") }.validate().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub struct DateRange<T> {
+    pub from: Option<T>,
+    pub to: Option<T>,
+}
+
+/// Range of UTC date times.
+///
+/// Only available when the `chrono` feature is activated.
+#[cfg(feature = "chrono")]
+pub type UtcDateRange = DateRange<chrono::DateTime<chrono::Utc>>;
+
+impl<T> DateRange<T> {
+    /// Build the SQL condition to filter the column passed by the range,
+    /// with the placeholders of the bind parameters numbered from the
+    /// number passed, e.g. `created_at BETWEEN $1 AND $2`, or
+    /// `created_at >= $1` if only `from` is set. `None` is returned
+    /// if no date is set. The values to bind are returned by
+    /// [`DateRange::values()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::DateRange;
+    ///
+    /// let range = DateRange { from: None, to: Some("2024-01-31") };
+    /// assert_eq!(range.to_sql("updated_at", 3).unwrap(), "updated_at <= $3");
+    /// assert_eq!(DateRange::<&str>::default().to_sql("updated_at", 1), None);
+    /// ```
+    pub fn to_sql(&self, column: &str, first_param: usize) -> Option<String> {
+        match (&self.from, &self.to) {
+            (Some(_), Some(_)) => Some(format!("{column} BETWEEN ${first_param} AND ${}", first_param + 1)),
+            (Some(_), None) => Some(format!("{column} >= ${first_param}")),
+            (None, Some(_)) => Some(format!("{column} <= ${first_param}")),
+            (None, None) => None,
+        }
+    }
+
+    /// The dates set, in the order of the bind parameters
+    /// of [`DateRange::to_sql()`].
+    pub fn values(&self) -> Vec<&T> {
+        self.from.iter().chain(self.to.iter()).collect()
+    }
+}
+
+impl<T: PartialOrd> Validate for DateRange<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) if from > to => {
+                let mut error = ValidationError::new(ErrorCode::DateRange.as_str());
+                error.message = Some(Cow::from("The start date cannot be after the end date"));
+                let mut errors = ValidationErrors::new();
+                errors.add("from", error);
+                Err(errors)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with a location and a distance, for
+/// "near me" searches, e.g. `/api/stores?lat=-34.6&lon=-58.4&radius_m=2000`.
+///
+/// The latitude has to be between -90 and 90, the longitude between
+/// -180 and 180, and the radius (in meters) between 1 and 1000 km.
+///
+/// ```
+/// use actix_contrib_rest::query::GeoQuery;
+/// use validator::Validate;
+///
+/// let geo = GeoQuery { lat: -34.6, lon: -58.4, radius_m: 2000.0 };
+/// assert!(geo.validate().is_ok());
+/// assert!(GeoQuery { lat: 91.0, ..geo }.validate().is_err());
+/// assert_eq!(geo.values(), (-34.6, -58.4, 2000.0));
+/// ```
+#[derive(Debug, Clone, Copy, Deserialize, Validate, PartialEq)]
+pub struct GeoQuery {
+    #[validate(range(min = -90.0, max = 90.0))]
+    pub lat: f64,
+    #[validate(range(min = -180.0, max = 180.0))]
+    pub lon: f64,
+    #[validate(range(min = 1.0, max = 1_000_000.0))]
+    pub radius_m: f64,
+}
+
+impl GeoQuery {
+    /// The values to bind to the parameters of the SQL conditions
+    /// of the struct: the latitude, the longitude and the radius.
+    pub fn values(&self) -> (f64, f64, f64) {
+        (self.lat, self.lon, self.radius_m)
+    }
+
+    /// Build the SQL condition to match the rows whose PostGIS `geography`
+    /// column passed is within the radius, with the placeholders of the
+    /// bind parameters numbered from the number passed, in the order
+    /// of [`GeoQuery::values()`].
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::GeoQuery;
+    ///
+    /// let geo = GeoQuery { lat: -34.6, lon: -58.4, radius_m: 2000.0 };
+    /// assert_eq!(
+    ///     geo.st_dwithin_condition("location", 1),
+    ///     "ST_DWithin(location, ST_SetSRID(ST_MakePoint($2, $1), 4326)::geography, $3)"
+    /// );
+    /// ```
+    pub fn st_dwithin_condition(&self, column: &str, first_param: usize) -> String {
+        let (lat, lon, radius) = (first_param, first_param + 1, first_param + 2);
+        format!("ST_DWithin({column}, ST_SetSRID(ST_MakePoint(${lon}, ${lat}), 4326)::geography, ${radius})")
+    }
+
+    /// Same as [`GeoQuery::st_dwithin_condition()`], but for tables with
+    /// the latitude and the longitude in two columns, using the functions
+    /// of the `earthdistance` extension of Postgres, with the
+    /// `earth_box` check first so the GiST indexes can be used.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::GeoQuery;
+    ///
+    /// let geo = GeoQuery { lat: -34.6, lon: -58.4, radius_m: 2000.0 };
+    /// assert_eq!(
+    ///     geo.earth_distance_condition("lat", "lon", 2),
+    ///     "earth_box(ll_to_earth($2, $3), $4) @> ll_to_earth(lat, lon) \
+    ///      AND earth_distance(ll_to_earth($2, $3), ll_to_earth(lat, lon)) <= $4"
+    /// );
+    /// ```
+    pub fn earth_distance_condition(&self, lat_column: &str, lon_column: &str, first_param: usize) -> String {
+        let (lat, lon, radius) = (first_param, first_param + 1, first_param + 2);
+        let point = format!("ll_to_earth(${lat}, ${lon})");
+        let row = format!("ll_to_earth({lat_column}, {lon_column})");
+        format!("earth_box({point}, ${radius}) @> {row} AND earth_distance({point}, {row}) <= ${radius}")
+    }
+}
+
+/// Struct used to deserialize with `serde` query strings
+/// from a request URL with the `tz` argument, the time zone to
+/// render the dates in, e.g. `/api/reports/sales?tz=America/New_York`,
+/// so date-bucketed reports match the days of the user. If not set,
+/// the dates are rendered in UTC.
+///
+/// With the `chrono-tz` feature the zone is validated against the IANA
+/// time zone database, otherwise only its characters are validated.
+///
+/// ```
+/// use actix_contrib_rest::query::TimeZoneQuery;
+/// use validator::Validate;
+///
+/// let tz = TimeZoneQuery { tz: Some("America/New_York".to_owned()) };
+/// assert!(tz.validate().is_ok());
+/// assert!(TimeZoneQuery { tz: Some("UTC'; --".to_owned()) }.validate().is_err());
+/// assert_eq!(tz.zone(), "America/New_York");
+/// assert_eq!(TimeZoneQuery::default().zone(), "UTC");
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Validate, PartialEq, Eq)]
+pub struct TimeZoneQuery {
+    #[validate(custom(function = "validate_time_zone"))]
+    pub tz: Option<String>,
+}
+
+impl TimeZoneQuery {
+    /// The time zone requested, or `"UTC"` if not set,
+    /// the value to bind to the parameter of [`TimeZoneQuery::at_time_zone()`].
+    pub fn zone(&self) -> &str {
+        self.tz.as_deref().unwrap_or("UTC")
+    }
+
+    /// The time zone requested parsed, or UTC if not set.
+    ///
+    /// Only available when the `chrono-tz` feature is activated.
+    #[cfg(feature = "chrono-tz")]
+    pub fn parse_zone(&self) -> Option<chrono_tz::Tz> {
+        self.zone().parse().ok()
+    }
+
+    /// Build the SQL expression to convert the `timestamptz` column passed
+    /// into the local time of the zone, with the placeholder of the bind
+    /// parameter with the number passed, e.g. to truncate the dates to
+    /// the days of the user with `date_trunc('day', ...)`.
+    ///
+    /// ```
+    /// use actix_contrib_rest::query::TimeZoneQuery;
+    ///
+    /// let tz = TimeZoneQuery { tz: Some("America/New_York".to_owned()) };
+    /// assert_eq!(tz.at_time_zone("created_at", 1), "created_at AT TIME ZONE $1");
+    /// ```
+    pub fn at_time_zone(&self, column: &str, param: usize) -> String {
+        format!("{column} AT TIME ZONE ${param}")
+    }
+}
+
+/// Validate the time zone passed is in the IANA time zone database.
+#[cfg(feature = "chrono-tz")]
+fn validate_time_zone(tz: &str) -> Result<(), ValidationError> {
+    match tz.parse::<chrono_tz::Tz>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new(ErrorCode::TimeZone.as_str())),
+    }
+}
+
+/// Validate the time zone passed looks like an IANA time zone name,
+/// e.g. `America/New_York` or `Etc/GMT+3`.
+#[cfg(not(feature = "chrono-tz"))]
+fn validate_time_zone(tz: &str) -> Result<(), ValidationError> {
+    let is_valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+');
+    if tz.is_empty() || !tz.chars().all(is_valid_char) {
+        return Err(ValidationError::new(ErrorCode::TimeZone.as_str()));
+    }
+    Ok(())
+}
+
+/// Opaque cursor tokens for keyset (cursor-based) pagination, see
+/// [`CursorPage`](crate::page::CursorPage). The values of the last row
+/// (e.g. the sort column and the ID) are serialized and signed with
+/// HMAC-SHA256 with the secret passed, so clients cannot tamper with
+/// them. The values are not encrypted, so don't use cursors for values
+/// that cannot be read by the clients.
+///
+/// The tokens have the form `<values>.<signature>`, both encoded
+/// with URL-safe base64, so they can be passed in query strings.
+///
+/// Only available when the `cursor` feature is activated.
+///
+/// ```
+/// use actix_contrib_rest::query::Cursor;
+///
+/// let secret = b"s3cr3t";
+/// let token = Cursor::encode(&("2024-01-31", 42), secret).unwrap();
+/// let (created_at, id): (String, i64) = Cursor::decode(&token, secret).unwrap();
+/// assert_eq!((created_at.as_str(), id), ("2024-01-31", 42));
+/// assert!(Cursor::decode::<(String, i64)>(&token, b"other").is_err());
+/// ```
+#[cfg(feature = "cursor")]
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor;
+
+#[cfg(feature = "cursor")]
+impl Cursor {
+    /// Encode the values passed into a token, signed with the secret.
+    /// An [`AppError::Unexpected`] error is returned if
+    /// the values cannot be serialized.
+    pub fn encode<T: Serialize + ?Sized>(values: &T, secret: &[u8]) -> AppResult<String> {
+        let data = serde_json::to_vec(values).map_err(|e| AppError::Unexpected(e.into()))?;
+        let signature = Self::mac(secret, &data).finalize().into_bytes();
+        Ok(format!("{}.{}", URL_SAFE_NO_PAD.encode(data), URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// Decode the values of the token passed, checking it was signed
+    /// with the secret. Otherwise an `HTTP 400 Bad Request` error
+    /// is returned with the `"invalid_cursor"` code.
+    pub fn decode<T: DeserializeOwned>(token: &str, secret: &[u8]) -> AppResult<T> {
+        let invalid = || {
+            AppError::Validation(Some(ErrorCode::InvalidCursor.as_str()), "Invalid cursor".to_owned())
+        };
+        let (data, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let data = URL_SAFE_NO_PAD.decode(data).map_err(|_| invalid())?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        Self::mac(secret, &data).verify_slice(&signature).map_err(|_| invalid())?;
+        serde_json::from_slice(&data).map_err(|_| invalid())
+    }
+
+    fn mac(secret: &[u8], data: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(data);
+        mac
+    }
 }
@@ -1,10 +1,17 @@
 //! Handlers to manage HTTP responses.
 
-use crate::result::ValidationErrorPayload;
+use crate::result::{apply_field_casing, AppError, ErrorCode, ErrorContext, ValidationErrorPayload};
 
+use actix_web::dev::ServiceResponse;
 use actix_web::error::InternalError;
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header::LOCATION;
+use actix_web::http::StatusCode;
+use actix_web::middleware::ErrorHandlerResponse;
+use actix_web::{web, HttpRequest, HttpResponse, Route};
 use actix_web_validator::Error;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt::Display;
 
 /// Function to handle validation errors when serializing the request payload (JSON body),
 /// or the query string, generating an HTTP 400 error with a JSON body
@@ -61,16 +68,345 @@ use actix_web_validator::Error;
 ///   }
 /// }
 /// ```
-pub fn json_error_handler(err: Error, _req: &HttpRequest) -> actix_web::error::Error {
-    let json_error = match &err {
+pub fn json_error_handler(err: Error, req: &HttpRequest) -> actix_web::error::Error {
+    let (status, payload) = match &err {
         Error::Validate(error) =>
-            HttpResponse::BadRequest().json(ValidationErrorPayload::from(error)),
+            (StatusCode::BAD_REQUEST, ValidationErrorPayload::from(error)),
         Error::JsonPayloadError(error) =>
-            HttpResponse::UnprocessableEntity()
-                .json(ValidationErrorPayload::new(error.to_string())),
+            (StatusCode::UNPROCESSABLE_ENTITY, ValidationErrorPayload::new(error.to_string())),
         _ =>
-            HttpResponse::BadRequest()
-                .json(ValidationErrorPayload::new(err.to_string())),
+            (StatusCode::BAD_REQUEST, ValidationErrorPayload::new(err.to_string())),
     };
+    error_response(err, req, status, payload)
+}
+
+/// Function to handle validation errors when deserializing the query string,
+/// generating an HTTP 400 error with the same JSON body of [`json_error_handler()`],
+/// e.g. for invalid `page_size` values or malformed filters. It has to be configured
+/// with the [`QueryConfig`](https://docs.rs/actix-web-validator/latest/actix_web_validator/struct.QueryConfig.html)
+/// extractor from the [actix-web-validator](https://docs.rs/actix-web-validator) validator crate.
+/// # Example
+/// ```
+/// use actix_web::{web, App};
+/// use actix_web::HttpResponse;
+/// use actix_web::Responder;
+/// use actix_web_validator::{Query, QueryConfig};
+/// use actix_contrib_rest::query::QuerySearch;
+/// use actix_contrib_rest::response::query_error_handler;
+///
+/// async fn get_handler(query: Query<QuerySearch>) -> impl Responder {
+///     // ...
+///     HttpResponse::Ok()
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/api")
+///             // ...
+///             .app_data(QueryConfig::default().error_handler(query_error_handler))
+///             .route(web::get().to(get_handler))
+///     );
+/// }
+/// ```
+/// If the query string cannot be deserialized, e.g. `?page_size=-1`, the response will look like:
+/// ```json
+/// {
+///   "code": "validation_error",
+///   "error": "Invalid query string: invalid value -1, expected a non-negative number"
+/// }
+/// ```
+pub fn query_error_handler(err: Error, req: &HttpRequest) -> actix_web::error::Error {
+    let payload = match &err {
+        Error::Validate(error) => ValidationErrorPayload::from(error),
+        _ => ValidationErrorPayload::with_code(
+            ErrorCode::ValidationError.to_string(),
+            format!("Invalid query string: {err}"),
+        ),
+    };
+    error_response(err, req, StatusCode::BAD_REQUEST, payload)
+}
+
+/// Function to handle the errors when deserializing the path parameters, e.g. a
+/// non-UUID `id`, generating an HTTP 400 error with the same JSON body of
+/// [`json_error_handler()`], with a `field_errors` entry for the segment of the
+/// path that failed, instead of the plain-text `HTTP 404 Not Found` default response.
+/// It has to be configured with the [`PathConfig`](https://docs.rs/actix-web-validator/latest/actix_web_validator/struct.PathConfig.html)
+/// extractor from the [actix-web-validator](https://docs.rs/actix-web-validator) validator crate.
+/// # Example
+/// ```
+/// use actix_web::{web, App};
+/// use actix_web::HttpResponse;
+/// use actix_web::Responder;
+/// use actix_web_validator::{Path, PathConfig};
+/// use actix_contrib_rest::response::path_error_handler;
+/// use serde::Deserialize;
+/// use validator::Validate;
+///
+/// #[derive(Deserialize, Validate)]
+/// pub struct OrderPath {
+///     pub id: u32,
+/// }
+///
+/// async fn get_handler(path: Path<OrderPath>) -> impl Responder {
+///     // ...
+///     HttpResponse::Ok()
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/api/orders/{id}")
+///             // ...
+///             .app_data(PathConfig::default().error_handler(path_error_handler))
+///             .route(web::get().to(get_handler))
+///     );
+/// }
+/// ```
+/// If a segment cannot be deserialized, e.g. `/api/orders/abc`, the response will look like
+/// the following. The segment is only reported when it can be told apart
+/// unambiguously, otherwise the response has no field errors.
+/// ```json
+/// {
+///   "code": "validation_error",
+///   "error": "Invalid path parameter \"id\"",
+///   "field_errors": {
+///     "id": [
+///       {
+///         "code": "invalid_path",
+///         "message": "can not parse \"abc\" to a u32",
+///         "params": {
+///           "value": "abc"
+///         }
+///       }
+///     ]
+///   }
+/// }
+/// ```
+pub fn path_error_handler(err: Error, req: &HttpRequest) -> actix_web::error::Error {
+    let payload = match &err {
+        Error::Validate(error) => ValidationErrorPayload::from(error),
+        _ => {
+            let message = err.to_string();
+            let segments: Vec<(&str, &str)> = req.match_info().iter().collect();
+            // The deserialization error has no field info, so the segment is
+            // reported only if it's the only one, or the only one whose value
+            // is quoted in the error
+            let quoted: Vec<&(&str, &str)> = segments
+                .iter()
+                .filter(|(_, value)| message.contains(&format!("{value:?}")))
+                .collect();
+            let segment = match (segments.as_slice(), quoted.as_slice()) {
+                ([segment], _) => Some(segment),
+                (_, [segment]) => Some(*segment),
+                _ => None,
+            };
+            match segment {
+                Some((name, value)) => ValidationErrorPayload::with_code(
+                    ErrorCode::ValidationError.to_string(),
+                    format!("Invalid path parameter \"{name}\""),
+                )
+                .field(*name, ErrorCode::InvalidPath.as_str(), message, json!({ "value": value })),
+                None => ValidationErrorPayload::with_code(
+                    ErrorCode::ValidationError.to_string(),
+                    format!("Invalid path: {message}"),
+                ),
+            }
+        }
+    };
+    error_response(err, req, StatusCode::BAD_REQUEST, payload)
+}
+
+/// Function to handle validation errors when deserializing urlencoded forms,
+/// e.g. from HTML forms, generating the same responses of [`json_error_handler()`]:
+/// an HTTP 400 error with the field errors if the validations fail, or
+/// an HTTP 422 error if the payload cannot be deserialized. It has to be
+/// configured with the [`FormConfig`](https://docs.rs/actix-web-validator/latest/actix_web_validator/struct.FormConfig.html)
+/// extractor from the [actix-web-validator](https://docs.rs/actix-web-validator) validator crate.
+/// # Example
+/// ```
+/// use actix_web::{web, App};
+/// use actix_web::HttpResponse;
+/// use actix_web::Responder;
+/// use actix_web_validator::{Form, FormConfig};
+/// use actix_contrib_rest::response::form_error_handler;
+/// use serde::Deserialize;
+/// use validator::Validate;
+///
+/// #[derive(Deserialize, Validate)]
+/// pub struct FormPayload {
+///     #[validate(length(min = 3, max = 50))]
+///     pub name: String,
+///     // ...
+/// }
+///
+/// async fn post_handler(form: Form<FormPayload>) -> impl Responder {
+///     // ...
+///     HttpResponse::Ok()
+/// }
+///
+/// fn main() {
+///     let app = App::new().service(
+///         web::resource("/signup")
+///             // ...
+///             .app_data(FormConfig::default().error_handler(form_error_handler))
+///             .route(web::post().to(post_handler))
+///     );
+/// }
+/// ```
+pub fn form_error_handler(err: Error, req: &HttpRequest) -> actix_web::error::Error {
+    let (status, payload) = match &err {
+        Error::Validate(error) => (StatusCode::BAD_REQUEST, ValidationErrorPayload::from(error)),
+        Error::UrlEncodedError(error) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ValidationErrorPayload::new(error.to_string()),
+        ),
+        _ => (StatusCode::BAD_REQUEST, ValidationErrorPayload::new(err.to_string())),
+    };
+    error_response(err, req, status, payload)
+}
+
+/// Build the JSON response of the error with the payload passed,
+/// with the request ID and the locale of the request.
+fn error_response(
+    err: Error,
+    req: &HttpRequest,
+    status: StatusCode,
+    mut payload: ValidationErrorPayload,
+) -> actix_web::error::Error {
+    let ctx = ErrorContext::from_request(req);
+    payload.request_id = ctx.request_id;
+    if let Some(locale) = ctx.locale {
+        payload.localize(&locale);
+    }
+    let mut body = serde_json::to_value(payload).unwrap_or_default();
+    apply_field_casing(&mut body);
+    let json_error = HttpResponse::build(status).json(body);
     InternalError::from_response(err, json_error).into()
 }
+
+/// Function to handle the [`AppError`] errors returned by the endpoint handlers,
+/// rendering the response with the request context, e.g. honoring the `Accept`
+/// header (see [`AppError::response_for()`]). It has to be configured as the
+/// default handler of the [`ErrorHandlers`](https://docs.rs/actix-web/latest/actix_web/middleware/struct.ErrorHandlers.html)
+/// middleware. Any other error response is left untouched.
+/// # Example
+/// ```
+/// use actix_web::App;
+/// use actix_web::middleware::ErrorHandlers;
+/// use actix_contrib_rest::response::app_error_handler;
+///
+/// fn main() {
+///     let app = App::new()
+///         .wrap(ErrorHandlers::new().default_handler(app_error_handler));
+///         // .service(...)
+/// }
+/// ```
+pub fn app_error_handler<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let response = res
+        .response()
+        .error()
+        .and_then(|e| e.as_error::<AppError>())
+        .map(|err| err.response_for(res.request()));
+    match response {
+        Some(response) => {
+            let (req, _) = res.into_parts();
+            Ok(ErrorHandlerResponse::Response(
+                ServiceResponse::new(req, response).map_into_right_body(),
+            ))
+        }
+        None => Ok(ErrorHandlerResponse::Response(res.map_into_left_body())),
+    }
+}
+
+/// Service to register as default service of the app with `App::default_service()`,
+/// so the requests to unmatched routes get an `HTTP 404 Not Found` response
+/// with the same JSON payload of the [`AppError`] errors, instead of
+/// an empty body.
+/// # Example
+/// ```
+/// use actix_web::{web, App};
+/// use actix_contrib_rest::response::{default_method_not_allowed, default_not_found};
+///
+/// fn main() {
+///     let app = App::new()
+///         .service(
+///             web::resource("/api/sales")
+///                 // .route(web::get().to(...))
+///                 .default_service(default_method_not_allowed())
+///         )
+///         .default_service(default_not_found());
+/// }
+/// ```
+/// The response looks like:
+/// ```json
+/// {
+///   "code": "not_found",
+///   "error": "Path /api/sale not found"
+/// }
+/// ```
+pub fn default_not_found() -> Route {
+    web::to(|req: HttpRequest| async move {
+        AppError::Custom(
+            StatusCode::NOT_FOUND,
+            ValidationErrorPayload::with_code(
+                ErrorCode::NotFound.to_string(),
+                format!("Path {} not found", req.path()),
+            ),
+        )
+        .response_for(&req)
+    })
+}
+
+/// Service to register as default service of a resource with
+/// `Resource::default_service()`, so the requests with methods not handled
+/// by the resource get an `HTTP 405 Method Not Allowed` response with
+/// the same JSON payload of the [`AppError`] errors, instead of an empty body.
+///
+/// See [`default_not_found()`].
+pub fn default_method_not_allowed() -> Route {
+    web::to(|req: HttpRequest| async move {
+        AppError::Custom(
+            StatusCode::METHOD_NOT_ALLOWED,
+            ValidationErrorPayload::with_code(
+                ErrorCode::MethodNotAllowed.to_string(),
+                format!("Method {} not allowed for path {}", req.method(), req.path()),
+            ),
+        )
+        .response_for(&req)
+    })
+}
+
+/// Build an `HTTP 201 Created` response with the `Location` header set
+/// to the location passed, and the resource created serialized as JSON.
+///
+/// ```
+/// use actix_contrib_rest::response::created;
+/// use actix_web::http::StatusCode;
+/// use serde_json::json;
+///
+/// let res = created("/api/orders/15", json!({ "id": 15, "status": "open" }));
+/// assert_eq!(res.status(), StatusCode::CREATED);
+/// assert_eq!(res.headers().get("location").unwrap(), "/api/orders/15");
+/// ```
+pub fn created(location: &str, body: impl Serialize) -> HttpResponse {
+    HttpResponse::Created()
+        .insert_header((LOCATION, location))
+        .json(body)
+}
+
+/// Same as [`created()`], but with the location built from the path
+/// of the request and the ID of the resource created, e.g.
+/// `/api/orders/15` for a `POST /api/orders` request.
+///
+/// ```
+/// use actix_contrib_rest::response::created_with_id;
+/// use actix_web::test::TestRequest;
+/// use serde_json::json;
+///
+/// let req = TestRequest::post().uri("/api/orders/").to_http_request();
+/// let res = created_with_id(&req, 15, json!({ "id": 15, "status": "open" }));
+/// assert_eq!(res.headers().get("location").unwrap(), "/api/orders/15");
+/// ```
+pub fn created_with_id(req: &HttpRequest, id: impl Display, body: impl Serialize) -> HttpResponse {
+    let location = format!("{}/{id}", req.path().trim_end_matches('/'));
+    created(&location, body)
+}
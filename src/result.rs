@@ -1,29 +1,724 @@
 //! Types to manage errors in Actix apps.
+//!
+//! The settings of the module, e.g. [`set_problem_details()`] or
+//! [`set_error_body()`], are global to the process, shared by all the
+//! apps it serves, and are meant to be set at startup, before the
+//! server starts handling requests.
 
+use actix_http::error::{HttpError, PayloadError};
+use actix_web::dev::Payload;
+use actix_web::http::header::{
+    Accept, Header, HeaderName, HeaderValue, TryIntoHeaderPair, ACCEPT_LANGUAGE, RETRY_AFTER, WWW_AUTHENTICATE,
+};
 use actix_web::http::StatusCode;
-use actix_web::{HttpResponse, ResponseError};
-use log::error;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError};
+use actix_web::web::Bytes;
+use awc::ClientResponse;
+use futures_core::stream::Stream;
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "sqlx")]
 use sqlx::Error as SqlxError;
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::error::DatabaseError;
+#[cfg(feature = "sqlx-postgres")]
+use sqlx::postgres::PgDatabaseError;
 
-use std::collections::HashMap;
-use validator::{ValidationError, ValidationErrors};
+use serde_json::Value;
+use std::backtrace::BacktraceStatus;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+/// SQLSTATE code of the Postgres `unique_violation` error.
+#[cfg(feature = "sqlx-postgres")]
+const PG_UNIQUE_VIOLATION: &str = "23505";
+/// SQLSTATE code of the Postgres `foreign_key_violation` error.
+#[cfg(feature = "sqlx-postgres")]
+const PG_FOREIGN_KEY_VIOLATION: &str = "23503";
+/// SQLSTATE code of the Postgres `serialization_failure` error.
+#[cfg(feature = "sqlx-postgres")]
+const PG_SERIALIZATION_FAILURE: &str = "40001";
+/// SQLSTATE code of the Postgres `deadlock_detected` error.
+#[cfg(feature = "sqlx-postgres")]
+const PG_DEADLOCK_DETECTED: &str = "40P01";
+
+/// Read the setting guarded by the lock passed, even if the lock was poisoned
+/// by a thread that panicked while holding it, e.g. within a hook, so
+/// a panic cannot break all the error responses that follow.
+pub(crate) fn read_setting<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Same as [`read_setting()`], but to replace the setting.
+pub(crate) fn write_setting<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+static PROBLEM_DETAILS: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable the [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807)
+/// output mode. When enabled, [`AppError`] errors are serialized as
+/// [`ProblemDetailsPayload`] documents with the `application/problem+json`
+/// content type, instead of the default payloads.
+///
+/// It's disabled by default.
+///
+/// ```
+/// use actix_contrib_rest::result::{problem_details, set_problem_details};
+/// set_problem_details(true);
+/// assert!(problem_details());
+/// ```
+pub fn set_problem_details(enabled: bool) {
+    PROBLEM_DETAILS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807)
+/// output mode is enabled, see [`set_problem_details()`].
+pub fn problem_details() -> bool {
+    PROBLEM_DETAILS.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "sqlx-postgres")]
+static CONSTRAINT_FIELDS: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// Register the field name to report in the `field_errors` of the response
+/// when the database constraint passed is violated, e.g. a unique
+/// violation of the `users_email_key` constraint is reported as an
+/// error of the `email` field.
+///
+/// It should be called at startup for each constraint to map.
+///
+/// Only available when the `sqlx-postgres` feature is activated.
+///
+/// ```
+/// use actix_contrib_rest::result::register_constraint_field;
+/// register_constraint_field("users_email_key", "email");
+/// ```
+#[cfg(feature = "sqlx-postgres")]
+pub fn register_constraint_field(constraint: &str, field: &str) {
+    write_setting(&CONSTRAINT_FIELDS).insert(constraint.to_owned(), field.to_owned());
+}
+
+#[cfg(feature = "sqlx-postgres")]
+fn constraint_field(constraint: &str) -> Option<String> {
+    read_setting(&CONSTRAINT_FIELDS).get(constraint).cloned()
+}
+
+/// Parse the detail of a Postgres foreign key violation, like
+/// `Key (customer_id)=(5) is not present in table "customers".`,
+/// returning the column, the value and the table.
+#[cfg(feature = "sqlx-postgres")]
+fn parse_fk_detail(detail: &str) -> Option<(&str, &str, &str)> {
+    let (column, rest) = detail.strip_prefix("Key (")?.split_once(")=(")?;
+    let (value, rest) = rest.split_once(") ")?;
+    let table = rest.rsplit_once(" table \"")?.1.strip_suffix("\".")?;
+    Some((column, value, table))
+}
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable the debug mode. When enabled, the internal errors
+/// ([`AppError::Unexpected`] and `AppError::DB`) include in the payload
+/// the chain of errors and the backtrace (if captured) that caused them,
+/// see [`ErrorDebugPayload`].
+///
+/// It's disabled by default, and it should only be enabled in
+/// development environments, otherwise internal details of the
+/// system are exposed to the clients.
+///
+/// ```
+/// use actix_contrib_rest::result::{debug, set_debug};
+/// set_debug(cfg!(debug_assertions));
+/// assert_eq!(debug(), cfg!(debug_assertions));
+/// ```
+pub fn set_debug(enabled: bool) {
+    DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the debug mode is enabled, see [`set_debug()`].
+pub fn debug() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
+static REQUEST_ID_HEADER: RwLock<&str> = RwLock::new("x-request-id");
+
+/// Set the header used to read the ID of the requests, included in the error
+/// payloads as `request_id`, `X-Request-Id` by default. See [`ErrorContext`].
+pub fn set_request_id_header(header: &'static str) {
+    *write_setting(&REQUEST_ID_HEADER) = header;
+}
+
+/// ID of the request, that can be inserted in the request extensions,
+/// e.g. by a middleware that generates the IDs, to be included in
+/// the error payloads as `request_id`. See [`ErrorContext`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Context of the request used to render the errors, see [`ErrorBody`].
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    /// The ID of the request, taken from the [`RequestId`] in the request
+    /// extensions, or from the header set with [`set_request_id_header()`].
+    pub request_id: Option<String>,
+    /// The path of the request.
+    pub path: Option<String>,
+    /// The pattern of the route that matched the request, e.g. `/users/{id}`.
+    pub route: Option<String>,
+    /// The preferred locale of the client, taken from
+    /// the `Accept-Language` header, see [`Locale`].
+    pub locale: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(|id| id.0.clone())
+            .or_else(|| {
+                req.headers()
+                    .get(*read_setting(&REQUEST_ID_HEADER))
+                    .and_then(|v| v.to_str().ok())
+                    .map(String::from)
+            });
+        ErrorContext {
+            request_id,
+            path: Some(req.path().to_owned()),
+            route: req.match_pattern(),
+            locale: accept_language(req),
+        }
+    }
+}
+
+static REDACTED_FIELDS: RwLock<&[&str]> = RwLock::new(&["password", "secret", "token"]);
+
+/// Set the name of the fields whose values are masked in the `params` of
+/// the field errors, to avoid leaking sensitive values like passwords,
+/// by default: `password`, `secret` and `token`.
+///
+/// ```
+/// use actix_contrib_rest::result::set_redacted_fields;
+/// set_redacted_fields(&["password", "token", "card_number"]);
+/// ```
+pub fn set_redacted_fields(fields: &'static [&'static str]) {
+    *write_setting(&REDACTED_FIELDS) = fields;
+}
+
+/// Mask the `value` param of the errors if the field has to be redacted.
+fn redact(field: &str, mut errors: Vec<ValidationError>) -> Vec<ValidationError> {
+    if read_setting(&REDACTED_FIELDS).contains(&field) {
+        for error in errors.iter_mut().filter(|e| e.params.contains_key("value")) {
+            error.params.insert(Cow::from("value"), Value::from("[redacted]"));
+        }
+    }
+    errors
+}
+
+/// Casing of the field names of the error payloads,
+/// see [`set_field_casing()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldCasing {
+    /// E.g. `field_errors`, the default.
+    #[default]
+    SnakeCase,
+    /// E.g. `fieldErrors`.
+    CamelCase,
+}
+
+static FIELD_CASING: RwLock<FieldCasing> = RwLock::new(FieldCasing::SnakeCase);
+
+/// Set the casing of the field names of the error payloads, e.g.
+/// `fieldErrors` and `requestId` instead of `field_errors` and `request_id`
+/// with [`FieldCasing::CamelCase`], including the nested objects like the
+/// `params` of the field errors. The field names used as keys of
+/// `field_errors` are left as is. The casing is only applied to the
+/// payloads of [`DefaultErrorBody`], not to custom [`ErrorBody`] envelopes.
+///
+/// ```
+/// use actix_contrib_rest::result::{set_field_casing, FieldCasing};
+/// set_field_casing(FieldCasing::CamelCase);
+/// ```
+pub fn set_field_casing(casing: FieldCasing) {
+    *write_setting(&FIELD_CASING) = casing;
+}
+
+/// Rename the fields of the payload passed, and of its nested objects,
+/// with the casing set with [`set_field_casing()`]. It's called
+/// automatically when the response of an error is built with
+/// [`DefaultErrorBody`].
+///
+/// ```
+/// use actix_contrib_rest::result::{apply_field_casing, set_field_casing, FieldCasing};
+/// use serde_json::json;
+///
+/// set_field_casing(FieldCasing::CamelCase);
+/// let mut payload = json!({
+///     "error": "Validation error",
+///     "field_errors": { "first_name": [{ "code": "length", "params": { "min_length": 3 } }] }
+/// });
+/// apply_field_casing(&mut payload);
+/// assert_eq!(payload, json!({
+///     "error": "Validation error",
+///     "fieldErrors": { "first_name": [{ "code": "length", "params": { "minLength": 3 } }] }
+/// }));
+/// ```
+pub fn apply_field_casing(payload: &mut Value) {
+    fn rename(value: &mut Value, keep_keys: bool) {
+        match value {
+            Value::Object(map) => {
+                *map = std::mem::take(map)
+                    .into_iter()
+                    .map(|(key, mut value)| {
+                        rename(&mut value, key == "field_errors");
+                        (if keep_keys { key } else { camel_case(&key) }, value)
+                    })
+                    .collect();
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| rename(item, false)),
+            _ => {}
+        }
+    }
+    if *read_setting(&FIELD_CASING) == FieldCasing::SnakeCase {
+        return;
+    }
+    rename(payload, false);
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut out = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+    out
+}
+
+static DROP_PARAMS_WITH_MESSAGE: AtomicBool = AtomicBool::new(false);
+
+/// Set whether the `params` of the field errors are dropped when the error
+/// has a custom message, e.g. set with `#[validate(..., message = "...")]`,
+/// so clients only get the human-readable message. Disabled by default.
+///
+/// ```
+/// use actix_contrib_rest::result::set_drop_params_with_message;
+/// set_drop_params_with_message(true);
+/// ```
+pub fn set_drop_params_with_message(enabled: bool) {
+    DROP_PARAMS_WITH_MESSAGE.store(enabled, Ordering::Relaxed);
+}
+
+/// Drop the params of the errors with a custom message, if enabled
+/// with [`set_drop_params_with_message()`].
+fn drop_params(mut errors: Vec<ValidationError>) -> Vec<ValidationError> {
+    if DROP_PARAMS_WITH_MESSAGE.load(Ordering::Relaxed) {
+        for error in errors.iter_mut().filter(|e| e.message.is_some()) {
+            error.params.clear();
+        }
+    }
+    errors
+}
+
+/// Collect the field errors into `out`, recursively with the
+/// errors of nested structs and lists.
+fn flatten_errors(errors: &ValidationErrors, prefix: &str, out: &mut HashMap<String, Vec<ValidationError>>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() { field.to_string() } else { format!("{prefix}.{field}") };
+        match kind {
+            ValidationErrorsKind::Field(errs) => {
+                out.insert(path, drop_params(redact(field, errs.clone())));
+            }
+            ValidationErrorsKind::Struct(nested) => flatten_errors(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (i, nested) in items {
+                    flatten_errors(nested, &format!("{path}[{i}]"), out);
+                }
+            }
+        }
+    }
+}
+
+/// Error codes used by the crate to identify the errors in the payloads,
+/// e.g. `"not_found"` for [`AppError::ResourceNotFound`] errors, including
+/// the codes of the `field_errors` entries, e.g. `"invalid_sort"`.
+///
+/// Apps can register their own codes (e.g. the ones used with
+/// [`AppError::Validation`]) with [`register_error_code()`], to be
+/// exported along with these in [`error_codes_catalog()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    ValidationError,
+    NotFound,
+    AlreadyExists,
+    Conflict,
+    TooManyRequests,
+    Unavailable,
+    Unauthorized,
+    Forbidden,
+    MethodNotAllowed,
+    InvalidReference,
+    Gone,
+    PayloadTooLarge,
+    UpstreamError,
+    StaleVersion,
+    Unique,
+    ForeignKey,
+    InvalidPage,
+    InvalidCursor,
+    InvalidArgument,
+    InvalidValue,
+    Range,
+    PageAndOffset,
+    InvalidSort,
+    InvalidGroupBy,
+    InvalidFilter,
+    InvalidFilterField,
+    InvalidFilterOp,
+    InvalidFields,
+    InvalidInclude,
+    InvalidPath,
+    DateRange,
+    TimeZone,
+}
+
+impl ErrorCode {
+    /// All the error codes used by the crate.
+    pub const ALL: &'static [ErrorCode] = &[
+        Self::ValidationError,
+        Self::NotFound,
+        Self::AlreadyExists,
+        Self::Conflict,
+        Self::TooManyRequests,
+        Self::Unavailable,
+        Self::Unauthorized,
+        Self::Forbidden,
+        Self::MethodNotAllowed,
+        Self::InvalidReference,
+        Self::Gone,
+        Self::PayloadTooLarge,
+        Self::UpstreamError,
+        Self::StaleVersion,
+        Self::Unique,
+        Self::ForeignKey,
+        Self::InvalidPage,
+        Self::InvalidCursor,
+        Self::InvalidArgument,
+        Self::InvalidValue,
+        Self::Range,
+        Self::PageAndOffset,
+        Self::InvalidSort,
+        Self::InvalidGroupBy,
+        Self::InvalidFilter,
+        Self::InvalidFilterField,
+        Self::InvalidFilterOp,
+        Self::InvalidFields,
+        Self::InvalidInclude,
+        Self::InvalidPath,
+        Self::DateRange,
+        Self::TimeZone,
+    ];
+
+    /// The code as serialized in the payloads.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ValidationError => "validation_error",
+            Self::NotFound => "not_found",
+            Self::AlreadyExists => "already_exists",
+            Self::Conflict => "conflict",
+            Self::TooManyRequests => "too_many_requests",
+            Self::Unavailable => "unavailable",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::InvalidReference => "invalid_reference",
+            Self::Gone => "gone",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::UpstreamError => "upstream_error",
+            Self::StaleVersion => "stale_version",
+            Self::Unique => "unique",
+            Self::ForeignKey => "foreign_key",
+            Self::InvalidPage => "invalid_page",
+            Self::InvalidCursor => "invalid_cursor",
+            Self::InvalidArgument => "invalid_argument",
+            Self::InvalidValue => "invalid_value",
+            Self::Range => "range",
+            Self::PageAndOffset => "page_and_offset",
+            Self::InvalidSort => "invalid_sort",
+            Self::InvalidGroupBy => "invalid_group_by",
+            Self::InvalidFilter => "invalid_filter",
+            Self::InvalidFilterField => "invalid_filter_field",
+            Self::InvalidFilterOp => "invalid_filter_op",
+            Self::InvalidFields => "invalid_fields",
+            Self::InvalidInclude => "invalid_include",
+            Self::InvalidPath => "invalid_path",
+            Self::DateRange => "date_range",
+            Self::TimeZone => "time_zone",
+        }
+    }
+
+    /// A human readable description of the error code.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::ValidationError => "The request payload has invalid fields",
+            Self::NotFound => "The resource requested was not found or was removed",
+            Self::AlreadyExists => "The resource already exists",
+            Self::Conflict => "The request conflicts with the current state of the resource",
+            Self::TooManyRequests => "Too many requests were sent in a given amount of time",
+            Self::Unavailable => "The service is temporarily unavailable",
+            Self::Unauthorized => "Valid authentication credentials are required",
+            Self::Forbidden => "Access to the resource is not allowed",
+            Self::MethodNotAllowed => "The method is not allowed for the resource",
+            Self::InvalidReference => "A resource referenced is invalid or is still referenced",
+            Self::Gone => "The resource was permanently removed",
+            Self::PayloadTooLarge => "The request payload exceeds the size limit",
+            Self::UpstreamError => "A service the request depends on failed or timed out",
+            Self::StaleVersion => "The version of the resource sent is not the current one",
+            Self::Unique => "A field has a value already used by another resource",
+            Self::ForeignKey => "A field references a resource that doesn't exist",
+            Self::InvalidPage => "The pagination metadata of a page is inconsistent",
+            Self::InvalidCursor => "The pagination cursor is malformed or was tampered with",
+            Self::InvalidArgument => "A query string argument has an invalid value",
+            Self::InvalidValue => "A query string value cannot be parsed into the expected type",
+            Self::Range => "A value is out of the allowed range",
+            Self::PageAndOffset => "The page and the offset arguments cannot be set at the same time",
+            Self::InvalidSort => "Sorting by a field not allowed",
+            Self::InvalidGroupBy => "Grouping by a field not allowed",
+            Self::InvalidFilter => "A filter expression is malformed",
+            Self::InvalidFilterField => "Filtering by a field not allowed",
+            Self::InvalidFilterOp => "A filter operator is unknown or not allowed for the field",
+            Self::InvalidFields => "Requesting fields not allowed",
+            Self::InvalidInclude => "Including relations not allowed",
+            Self::InvalidPath => "A segment of the path has an invalid value",
+            Self::DateRange => "The start of a date range is after its end",
+            Self::TimeZone => "The time zone is not a valid IANA time zone",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+static ERROR_CODES: RwLock<BTreeMap<&str, &str>> = RwLock::new(BTreeMap::new());
+
+/// Register an error code used by the app, with its description,
+/// to be exported in the [`error_codes_catalog()`].
+///
+/// It should be called at startup for each code.
+pub fn register_error_code(code: &'static str, description: &'static str) {
+    write_setting(&ERROR_CODES).insert(code, description);
+}
+
+/// All the error codes known, the ones used by the crate ([`ErrorCode`])
+/// and the ones registered with [`register_error_code()`], as a JSON array
+/// sorted by code, e.g. to be served to the frontend apps to map the
+/// codes into UI messages.
+///
+/// ```
+/// use actix_contrib_rest::result::{error_codes_catalog, register_error_code};
+/// use serde_json::json;
+///
+/// register_error_code("insufficient_funds", "The account doesn't have enough funds");
+/// let catalog = error_codes_catalog();
+/// assert!(catalog.as_array().unwrap().contains(&json!({
+///     "code": "insufficient_funds",
+///     "description": "The account doesn't have enough funds"
+/// })));
+/// ```
+pub fn error_codes_catalog() -> Value {
+    let mut codes: BTreeMap<&str, &str> = ErrorCode::ALL
+        .iter()
+        .map(|code| (code.as_str(), code.description()))
+        .collect();
+    codes.extend(read_setting(&ERROR_CODES).iter());
+    Value::Array(
+        codes
+            .into_iter()
+            .map(|(code, description)| serde_json::json!({ "code": code, "description": description }))
+            .collect(),
+    )
+}
+
+static NOT_FOUND_TEMPLATE: RwLock<Cow<str>> =
+    RwLock::new(Cow::Borrowed("{resource} with {attribute} equals to \"{value}\" not found or was removed"));
+
+/// Set the template of the message of the [`AppError::ResourceNotFound`] errors,
+/// where the `{resource}`, `{attribute}` and `{value}` placeholders are replaced
+/// by the values of the error. By default:
+/// `{resource} with {attribute} equals to "{value}" not found or was removed`.
+///
+/// ```
+/// use actix_contrib_rest::result::{set_not_found_template, AppError};
+///
+/// set_not_found_template("The {resource} {value} doesn't exist");
+/// let error = AppError::ResourceNotFound { resource: "order", attribute: "id", value: "12".to_owned() };
+/// assert_eq!(error.to_string(), "The order 12 doesn't exist");
+/// ```
+pub fn set_not_found_template(template: impl Into<String>) {
+    *write_setting(&NOT_FOUND_TEMPLATE) = Cow::Owned(template.into());
+}
+
+fn attributes_message(attributes: &[(&str, String)]) -> String {
+    attributes
+        .iter()
+        .map(|(attribute, value)| format!("{attribute} equals to \"{value}\""))
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn not_found_message(resource: &str, attribute: &str, value: &str) -> String {
+    read_setting(&NOT_FOUND_TEMPLATE)
+        .replace("{resource}", resource)
+        .replace("{attribute}", attribute)
+        .replace("{value}", value)
+}
 
 /// Use to serialize a simple error with a static message.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct InternalErrorPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub code: Option<&'static str>,
-    pub error: &'static str,
+    pub code: Option<Cow<'static, str>>,
+    pub error: Cow<'static, str>,
+    /// Whether the request can be retried, e.g. after a database
+    /// timeout, see [`AppError::is_retryable()`]. Only set
+    /// for server errors (`HTTP 5xx`), see [`DefaultErrorBody`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retryable: Option<bool>,
+    /// The ID of the request that failed, if known, see [`ErrorContext`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Details of the error, only set in debug mode, see [`set_debug()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<ErrorDebugPayload>,
 }
 
 impl InternalErrorPayload {
     pub fn init(error: &'static str) -> Self {
         Self {
             code: None,
-            error,
+            error: Cow::Borrowed(error),
+            retryable: None,
+            request_id: None,
+            debug: None,
+        }
+    }
+
+    /// Deserialize the payload from the body of an error response, e.g. from
+    /// other services built with this crate. If the body cannot be
+    /// deserialized, [`AppError::Unexpected`] is returned.
+    ///
+    /// See [`ValidationErrorPayload::from_response()`].
+    pub async fn from_response<S>(res: &mut ClientResponse<S>) -> Result<Self>
+    where
+        S: Stream<Item = core::result::Result<Bytes, PayloadError>> + Unpin,
+    {
+        res.json::<Self>().await.map_err(|e| AppError::Unexpected(e.into()))
+    }
+}
+
+/// Use to serialize the details of an internal error in debug mode,
+/// see [`set_debug()`].
+///
+/// An error serialized as JSON looks like:
+///
+/// ```json
+/// {
+///   "chain": [
+///     "Failed to read the config file",
+///     "No such file or directory (os error 2)"
+///   ],
+///   "backtrace": "   0: std::backtrace::Backtrace::capture\n..."
+/// }
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ErrorDebugPayload {
+    /// The messages of the error and all its sources.
+    pub chain: Vec<String>,
+    /// The backtrace of the error, only available for [`AppError::Unexpected`]
+    /// errors when the backtrace was captured (e.g. `RUST_BACKTRACE=1`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+impl From<&AppError> for ErrorDebugPayload {
+    fn from(error: &AppError) -> Self {
+        let chain = error.chain();
+        let backtrace = match error {
+            AppError::Unexpected(e) if e.backtrace().status() == BacktraceStatus::Captured => {
+                Some(e.backtrace().to_string())
+            }
+            _ => None,
+        };
+        ErrorDebugPayload { chain, backtrace }
+    }
+}
+
+/// Use to serialize an error as a "problem detail" document
+/// following the [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807).
+///
+/// It's only used when the mode is enabled with [`set_problem_details()`].
+/// An error serialized as JSON looks like:
+///
+/// ```json
+/// {
+///   "type": "about:blank",
+///   "title": "Not Found",
+///   "status": 404,
+///   "detail": "order with id equals to \"123\" not found or was removed",
+///   "code": "not_found"
+/// }
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProblemDetailsPayload {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Extension member with the error code, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Extension member with the ID of the request that failed, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Extension member with the details of internal errors,
+    /// only set in debug mode, see [`set_debug()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<ErrorDebugPayload>,
+}
+
+impl From<&AppError> for ProblemDetailsPayload {
+    fn from(error: &AppError) -> Self {
+        let status = error.status_code();
+        let title = status.canonical_reason().unwrap_or("Unknown error");
+        ProblemDetailsPayload {
+            problem_type: "about:blank".to_owned(),
+            title: title.to_owned(),
+            status: status.as_u16(),
+            // Internal errors are not detailed to avoid leaking internal details
+            detail: if error.is_internal() { None } else { Some(error.to_string()) },
+            instance: None,
+            code: error.code().map(String::from),
+            request_id: None,
+            debug: if debug() && error.is_internal() { Some(ErrorDebugPayload::from(error)) } else { None },
         }
     }
 }
@@ -47,13 +742,48 @@ impl InternalErrorPayload {
 ///   }
 /// }
 /// ```
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ValidationErrorPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
     pub error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schemars", schemars(with = "Option<HashMap<String, Vec<FieldErrorSchema>>>"))]
     pub field_errors: Option<HashMap<String, Vec<ValidationError>>>,
+    /// List of errors not related to a field, e.g. business
+    /// rules violations, see [`AppError::Validations`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<ErrorMessagePayload>>,
+    /// Free-form details of the error, e.g. limits exceeded or
+    /// the IDs of the resources in conflict.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// The ID of the request that failed, if known, see [`ErrorContext`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Schema of the field errors of [`ValidationErrorPayload`],
+/// as serialized by `validator`.
+#[cfg(feature = "schemars")]
+#[derive(schemars::JsonSchema)]
+#[schemars(rename = "FieldError")]
+#[allow(dead_code)]
+struct FieldErrorSchema {
+    code: String,
+    message: Option<String>,
+    params: HashMap<String, Value>,
+}
+
+/// Use to serialize an error message with an optional
+/// code, within a list of errors.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ErrorMessagePayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub error: String,
 }
 
 impl ValidationErrorPayload {
@@ -62,6 +792,9 @@ impl ValidationErrorPayload {
             code: None,
             error: detail,
             field_errors: None,
+            errors: None,
+            details: None,
+            request_id: None,
         }
     }
 
@@ -70,23 +803,181 @@ impl ValidationErrorPayload {
             code: Some(code_error),
             error: detail,
             field_errors: None,
+            errors: None,
+            details: None,
+            request_id: None,
+        }
+    }
+
+    /// Build the payload with the code, the error message and
+    /// the details passed.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::ValidationErrorPayload;
+    /// use serde_json::json;
+    ///
+    /// let payload = ValidationErrorPayload::with_details(
+    ///     "limit_exceeded".to_owned(),
+    ///     "Daily limit exceeded".to_owned(),
+    ///     json!({ "limit": 1000, "current": 1250 }),
+    /// );
+    /// assert_eq!(payload.details.unwrap()["limit"], 1000);
+    /// ```
+    pub fn with_details(code_error: String, detail: String, details: Value) -> Self {
+        ValidationErrorPayload {
+            details: Some(details),
+            ..Self::with_code(code_error, detail)
+        }
+    }
+
+    /// Add a field error with the code, the message and the params passed
+    /// (a JSON object), in the same structure of the `field_errors`
+    /// generated from the `validator` errors.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::ValidationErrorPayload;
+    /// use serde_json::json;
+    ///
+    /// let payload = ValidationErrorPayload::with_code("validation_error".to_owned(), "Validations error".to_owned())
+    ///     .field("email", "unique", "Email already registered", json!({ "value": "john@example.com" }))
+    ///     .field("age", "range", "Too young", json!({ "min": 18, "value": 16 }));
+    /// let field_errors = payload.field_errors.unwrap();
+    /// assert_eq!(field_errors["email"][0].code, "unique");
+    /// assert_eq!(field_errors["age"][0].params["min"], 18);
+    /// ```
+    pub fn field(
+        mut self,
+        name: impl Into<String>,
+        code: &'static str,
+        message: impl Into<Cow<'static, str>>,
+        params: Value,
+    ) -> Self {
+        let mut error = ValidationError::new(code);
+        error.message = Some(message.into());
+        if let Value::Object(params) = params {
+            for (param, value) in params {
+                error.params.insert(Cow::Owned(param), value);
+            }
         }
+        self.field_errors
+            .get_or_insert_with(HashMap::new)
+            .entry(name.into())
+            .or_default()
+            .push(error);
+        self
+    }
+
+    /// Deserialize the payload from the body of an error response, e.g. from
+    /// other services built with this crate. If the body cannot be
+    /// deserialized, [`AppError::Unexpected`] is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use actix_contrib_rest::result::{Result, ValidationErrorPayload};
+    /// use awc::Client;
+    ///
+    /// async fn create_sale(client: &Client) -> Result<()> {
+    ///     let mut res = client.post("http://sales-service/sales")
+    ///         .send_json(&serde_json::json!({ "customer_id": 1123 }))
+    ///         .await
+    ///         .unwrap();
+    ///     if res.status().is_client_error() {
+    ///         let payload = ValidationErrorPayload::from_response(&mut res).await?;
+    ///         println!("Error creating sale: {}", payload.error);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn from_response<S>(res: &mut ClientResponse<S>) -> Result<Self>
+    where
+        S: Stream<Item = core::result::Result<Bytes, PayloadError>> + Unpin,
+    {
+        res.json::<Self>().await.map_err(|e| AppError::Unexpected(e.into()))
+    }
+
+    /// Translate the error message and the messages of the field errors
+    /// into the locale passed, using the catalog set with
+    /// [`set_message_catalog()`]. The messages without a
+    /// translation for its code are left untouched.
+    pub fn localize(&mut self, locale: &str) {
+        with_message_catalog(|catalog| {
+            if let Some(message) = self
+                .code
+                .as_deref()
+                .and_then(|code| catalog.message(locale, code, &HashMap::new()))
+            {
+                self.error = message;
+            }
+            for error in self.errors.iter_mut().flatten() {
+                if let Some(message) = error
+                    .code
+                    .as_deref()
+                    .and_then(|code| catalog.message(locale, code, &HashMap::new()))
+                {
+                    error.error = message;
+                }
+            }
+            for errors in self.field_errors.iter_mut().flat_map(|f| f.values_mut()) {
+                for error in errors {
+                    if let Some(message) = catalog.message(locale, &error.code, &error.params) {
+                        error.message = Some(Cow::Owned(message));
+                    }
+                }
+            }
+        });
     }
 }
 
 impl From<&ValidationErrors> for ValidationErrorPayload {
+    /// Build the payload with the field errors passed, including the errors
+    /// of nested structs and lists, with the fields named with dotted and
+    /// indexed paths, e.g. `address.street` or `items[2].qty`. The values of
+    /// the fields set with [`set_redacted_fields()`] are masked in the params.
+    ///
+    /// The custom messages of the errors, e.g. set with
+    /// `#[validate(..., message = "...")]`, are always kept in their entries
+    /// of the `field_errors`. Only when there is a single error in total is its
+    /// custom message used as the `error` of the payload as well, otherwise
+    /// the `error` is a generic message.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::ValidationErrorPayload;
+    /// use validator::Validate;
+    ///
+    /// #[derive(Validate)]
+    /// struct Signup {
+    ///     #[validate(email(message = "Enter a valid email"))]
+    ///     email: String,
+    ///     #[validate(range(min = 18, message = "You must be an adult"))]
+    ///     age: u8,
+    /// }
+    ///
+    /// let errors = Signup { email: "jhon".to_owned(), age: 20 }.validate().unwrap_err();
+    /// let payload = ValidationErrorPayload::from(&errors);
+    /// assert_eq!(payload.error, "Enter a valid email");
+    ///
+    /// let errors = Signup { email: "jhon".to_owned(), age: 12 }.validate().unwrap_err();
+    /// let payload = ValidationErrorPayload::from(&errors);
+    /// assert_eq!(payload.error, "Validations error");
+    /// let field_errors = payload.field_errors.unwrap();
+    /// assert_eq!(field_errors["email"][0].message.as_deref(), Some("Enter a valid email"));
+    /// assert_eq!(field_errors["age"][0].message.as_deref(), Some("You must be an adult"));
+    /// ```
     fn from(error: &ValidationErrors) -> Self {
         let mut errors: HashMap<String, Vec<ValidationError>> = HashMap::new();
-        errors.extend(
-            error
-                .field_errors()
-                .iter()
-                .map(|(k, v)| (String::from(*k), (*v).clone())),
-        );
+        flatten_errors(error, "", &mut errors);
+        let message = match errors.values().flatten().collect::<Vec<_>>().as_slice() {
+            [single] => single.message.as_ref().map_or("Validation error".to_owned(), |m| m.to_string()),
+            [] => "Validation error".to_owned(),
+            _ => "Validations error".to_owned(),
+        };
         ValidationErrorPayload {
-            code: Some("validation_error".to_owned()),
-            error: if errors.len() > 1 { "Validations error".to_owned() } else { "Validation error".to_owned() },
+            code: Some(ErrorCode::ValidationError.to_string()),
+            error: message,
             field_errors: Some(errors),
+            errors: None,
+            details: None,
+            request_id: None,
         }
     }
 }
@@ -131,11 +1022,45 @@ pub enum AppError {
     #[error("{1}")]
     Validation(Option<&'static str>, String),
 
+    /// Used to trigger several validations at once, e.g. when
+    /// business rules violations are collected before failing, each
+    /// with an optional code and the error message.
+    ///
+    /// These errors are processed as `HTTP 400 Bad Request`, with the
+    /// `"validation_error"` code, and all the errors in the `errors` list.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// let mut errors = Vec::new();
+    /// if sale.qty > product.stock {
+    ///     errors.push((Some("out_of_stock"), format!("Product {} is out of stock", product.id)));
+    /// }
+    /// if sale.total > customer.credit {
+    ///     errors.push((None, "Customer doesn't have enough credit".to_owned()));
+    /// }
+    /// if !errors.is_empty() {
+    ///     return Err(AppError::Validations(errors));
+    /// }
+    /// ```
+    #[error("{}", .0.iter().map(|(_, e)| e.as_str()).collect::<Vec<_>>().join("; "))]
+    Validations(Vec<(Option<&'static str>, String)>),
+
     #[cfg(feature = "sqlx")]
     /// Encapsulates a `SqlxError` error (database errors), like
     /// the DB is not accessible, time outs, and so on.
     ///
     /// These errors are processed as `HTTP 500 Internal Server Error`.
+    ///
+    /// With the `sqlx-postgres` feature, unique constraint violations
+    /// (SQLSTATE `23505`) are processed as `HTTP 409 Conflict` instead,
+    /// with the `"conflict"` code, and the field registered for the
+    /// constraint with [`register_constraint_field()`] (if any)
+    /// reported in the `field_errors`. Foreign key violations (SQLSTATE `23503`)
+    /// are processed as `HTTP 400 Bad Request`, with the `"invalid_reference"`
+    /// code, and the table and column of the reference reported
+    /// in the `field_errors`.
     /// # Example
     /// ```ignore, no_run
     /// use actix_contrib_rest::result::AppError;
@@ -169,13 +1094,40 @@ pub enum AppError {
     ///
     /// In the example above, the error message will be:
     /// *order with id equals to "123432" not found or was removed*.
-    #[error("{resource} with {attribute} equals to \"{value}\" not found or was removed")]
+    ///
+    /// The message can be customized with [`set_not_found_template()`].
+    #[error("{}", not_found_message(.resource, .attribute, .value))]
     ResourceNotFound {
         resource: &'static str,
         attribute: &'static str,
         value: String,
     },
 
+    /// Used when a resource requested by several attributes (e.g. a
+    /// composite key) cannot be found, or was deleted.
+    ///
+    /// These errors are processed as `HTTP 404 Not Found`, with the
+    /// `"not_found"` code, and the resource and the attributes
+    /// in the `details` of the payload.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// return Err(AppError::ResourceNotFoundBy {
+    ///     resource: "page",
+    ///     attributes: vec![("tenant_id", tenant_id.to_string()), ("slug", slug)],
+    /// });
+    /// ```
+    ///
+    /// In the example above, the error message will be:
+    /// *page with tenant_id equals to "12" and slug equals to "about" not found or was removed*.
+    #[error("{resource} with {} not found or was removed", attributes_message(.attributes))]
+    ResourceNotFoundBy {
+        resource: &'static str,
+        attributes: Vec<(&'static str, String)>,
+    },
+
     /// Used when a resource already exists in the system.
     ///
     /// These errors are processed as `HTTP 400 Bad Request`.
@@ -200,36 +1152,233 @@ pub enum AppError {
         value: String,
     },
 
-    /// Lacks valid authentication credentials for the requested resource.
+    /// Used when the request conflicts with the current state of a
+    /// resource, e.g. a duplicated entity.
     ///
-    /// These errors are processed as `HTTP 401 Unauthorized`.
+    /// These errors are processed as `HTTP 409 Conflict`,
+    /// with the `"conflict"` code.
     ///
     /// # Example
     /// ```ignore, no_run
     /// use actix_contrib_rest::result::AppError;
     /// // ...
-    /// return Err(AppError::Unauthorized(
-    ///     "Unauthorized access to ..."
-    /// ));
+    /// return Err(AppError::Conflict {
+    ///     resource: "user",
+    ///     attribute: "email",
+    ///     value: user.email.clone()
+    /// });
     /// ```
-    #[error("{0}")]
-    Unauthorized(&'static str),
-
-    /// Use to indicates that system understands the request but refuses
-    /// to authorize it.
     ///
-    /// These errors are processed as `HTTP 403 Forbidden`.
-    ///
-    /// # Example
-    /// ```ignore, no_run
-    /// use actix_contrib_rest::result::AppError;
+    /// In the example above, the error message will be:
+    /// *user with email "john@example.com" conflicts with an existing user*.
+    #[error("{resource} with {attribute} \"{value}\" conflicts with an existing {resource}")]
+    Conflict {
+        resource: &'static str,
+        attribute: &'static str,
+        value: String,
+    },
+
+    /// Used when a resource was permanently removed, e.g. a tombstoned
+    /// resource that will not be available again.
+    ///
+    /// These errors are processed as `HTTP 410 Gone`,
+    /// with the `"gone"` code.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// return Err(AppError::Gone {
+    ///     resource: "invoice",
+    ///     value: id.to_string(),
+    /// });
+    /// ```
+    ///
+    /// In the example above, the error message will be:
+    /// *invoice "1234" was permanently removed*.
+    #[error("{resource} \"{value}\" was permanently removed")]
+    Gone {
+        resource: &'static str,
+        value: String,
+    },
+
+    /// Used when the request payload exceeds the size limit (in bytes)
+    /// accepted, e.g. in upload endpoints.
+    ///
+    /// These errors are processed as `HTTP 413 Payload Too Large`, with the
+    /// `"payload_too_large"` code, and the limit in the `details` of the payload.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// if file.size > MAX_UPLOAD_SIZE {
+    ///     return Err(AppError::PayloadTooLarge { limit: MAX_UPLOAD_SIZE });
+    /// }
+    /// ```
+    #[error("Payload too large, the limit is {limit} bytes")]
+    PayloadTooLarge {
+        limit: usize,
+    },
+
+    /// Used when the version of a resource sent by the client (e.g. with the
+    /// `If-Match` header) is not the current one, in optimistic
+    /// concurrency control flows.
+    ///
+    /// These errors are processed as `HTTP 412 Precondition Failed`, with the
+    /// `"stale_version"` code, and the versions in the `details` of the payload.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// if if_match != order.etag() {
+    ///     return Err(AppError::PreconditionFailed {
+    ///         resource: "order",
+    ///         expected: if_match,
+    ///         actual: order.etag(),
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// In the example above, the error message will be:
+    /// *order version "v1" is stale, the current version is "v2"*.
+    #[error("{resource} version \"{expected}\" is stale, the current version is \"{actual}\"")]
+    PreconditionFailed {
+        resource: &'static str,
+        expected: String,
+        actual: String,
+    },
+
+    /// Used when the client sent too many requests in a given amount of time,
+    /// e.g. to propagate rate-limit errors from other services. If no message
+    /// is passed, `"Too many requests"` is used.
+    ///
+    /// These errors are processed as `HTTP 429 Too Many Requests`, with the
+    /// `"too_many_requests"` code, and the `Retry-After` header set
+    /// (in seconds) if `retry_after` is passed.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// use std::time::Duration;
+    /// // ...
+    /// return Err(AppError::TooManyRequests {
+    ///     retry_after: Some(Duration::from_secs(30)),
+    ///     message: None,
+    /// });
+    /// ```
+    #[error("{}", .message.as_deref().unwrap_or("Too many requests"))]
+    TooManyRequests {
+        retry_after: Option<Duration>,
+        message: Option<String>,
+    },
+
+    /// Used when the service is temporarily unavailable, e.g. during a
+    /// maintenance window or when a dependency is degraded.
+    ///
+    /// These errors are processed as `HTTP 503 Service Unavailable`, with the
+    /// `"unavailable"` code, and the `Retry-After` header set (in seconds) if
+    /// the duration is passed.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// use std::time::Duration;
+    /// // ...
+    /// return Err(AppError::Unavailable(
+    ///     "The service is under maintenance, try again later.",
+    ///     Some(Duration::from_secs(600))
+    /// ));
+    /// ```
+    #[error("{0}")]
+    Unavailable(&'static str, Option<Duration>),
+
+    /// Used when a service the request depends on (e.g. another HTTP API)
+    /// failed, with the status of the upstream response if any.
+    ///
+    /// These errors are processed as `HTTP 504 Gateway Timeout` if the
+    /// upstream status is `504 Gateway Timeout` or `408 Request Timeout`,
+    /// otherwise as `HTTP 502 Bad Gateway`, with the `"upstream_error"` code,
+    /// and the service and the upstream status in the `details` of the payload.
+    /// The cause is not exposed in the response.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// let res = client.get(url).send().await.map_err(AppError::upstream("billing"))?;
+    /// if res.status().is_server_error() {
+    ///     return Err(AppError::Upstream {
+    ///         service: "billing",
+    ///         status: Some(res.status()),
+    ///         source: anyhow!("Error fetching the invoices"),
+    ///     });
+    /// }
+    /// ```
+    #[error("Error calling the {service} service")]
+    Upstream {
+        service: &'static str,
+        status: Option<StatusCode>,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// Lacks valid authentication credentials for the requested resource.
+    /// If no message is passed, `"Unauthorized"` is used.
+    ///
+    /// These errors are processed as `HTTP 401 Unauthorized`,
+    /// with the `"unauthorized"` code.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// return Err(AppError::Unauthorized(
+    ///     Some("Unauthorized access to ...")
+    /// ));
+    /// ```
+    #[error("{}", .0.unwrap_or("Unauthorized"))]
+    Unauthorized(Option<&'static str>),
+
+    /// Use to indicates that system understands the request but refuses
+    /// to authorize it.
+    ///
+    /// These errors are processed as `HTTP 403 Forbidden`,
+    /// with the `"forbidden"` code.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
     /// // ...
     /// return Err(AppError::Forbidden(
-    ///     "Cannot access to ..."
+    ///     format!("Cannot access to the account {}", account.id)
     /// ));
     /// ```
     #[error("{0}")]
-    Forbidden(&'static str),
+    Forbidden(String),
+
+    /// Used to respond with any status code and a custom payload, for
+    /// edge cases not covered by the other variants.
+    ///
+    /// These errors are processed with the status code passed, and the
+    /// payload serialized as the response body.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::{AppError, ValidationErrorPayload};
+    /// use actix_web::http::StatusCode;
+    /// // ...
+    /// return Err(AppError::Custom(
+    ///     StatusCode::UNPROCESSABLE_ENTITY,
+    ///     ValidationErrorPayload::with_code(
+    ///         "unsupported_currency".to_owned(),
+    ///         format!("Currency {} is not supported", sale.currency),
+    ///     )
+    /// ));
+    /// ```
+    #[error("{}", .1.error)]
+    Custom(StatusCode, ValidationErrorPayload),
 
     /// Any other error that needs to be wrapped inside an AppError.
     ///
@@ -247,64 +1396,1289 @@ pub enum AppError {
     /// some_operation().map_err(|e| AppError::Unexpected(e.into()))?;
     /// ```
     #[error(transparent)]
-    Unexpected(#[from] anyhow::Error),
+    Unexpected(anyhow::Error),
+
+    /// Wraps an error with extra headers sent in the response, e.g.
+    /// `X-Error-Code` or `Deprecation`, so gateways and monitoring can
+    /// classify the errors without parsing the body. Use
+    /// [`AppError::with_header()`] to build it.
+    ///
+    /// These errors are processed as the error wrapped, with the headers added.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// return Err(AppError::Forbidden("The plan doesn't include exports".to_owned())
+    ///     .with_header(("X-Error-Code", "plan_limit")));
+    /// ```
+    #[error("{0}")]
+    WithHeaders(Box<AppError>, Vec<(HeaderName, HeaderValue)>),
+}
+
+impl AppError {
+    /// The error code used to identify the error in the payloads, if any,
+    /// e.g. `"not_found"` for [`AppError::ResourceNotFound`].
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::WithHeaders(error, _) => error.code(),
+            Self::Validation(code, _) => *code,
+            Self::Custom(_, payload) => payload.code.as_deref(),
+            Self::Validations(_) => Some(ErrorCode::ValidationError.as_str()),
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_UNIQUE_VIOLATION) => Some(ErrorCode::Conflict.as_str()),
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_FOREIGN_KEY_VIOLATION) => Some(ErrorCode::InvalidReference.as_str()),
+            Self::ResourceNotFound { resource: _, attribute: _, value: _ } => Some(ErrorCode::NotFound.as_str()),
+            Self::ResourceNotFoundBy { resource: _, attributes: _ } => Some(ErrorCode::NotFound.as_str()),
+            Self::ResourceAlreadyExists { resource: _, attribute: _, value: _ } => Some(ErrorCode::AlreadyExists.as_str()),
+            Self::Conflict { resource: _, attribute: _, value: _ } => Some(ErrorCode::Conflict.as_str()),
+            Self::Gone { resource: _, value: _ } => Some(ErrorCode::Gone.as_str()),
+            Self::PayloadTooLarge { limit: _ } => Some(ErrorCode::PayloadTooLarge.as_str()),
+            Self::PreconditionFailed { resource: _, expected: _, actual: _ } => Some(ErrorCode::StaleVersion.as_str()),
+            Self::TooManyRequests { retry_after: _, message: _ } => Some(ErrorCode::TooManyRequests.as_str()),
+            Self::Unavailable(_, _) => Some(ErrorCode::Unavailable.as_str()),
+            Self::Upstream { service: _, status: _, source: _ } => Some(ErrorCode::UpstreamError.as_str()),
+            Self::Unauthorized(_) => Some(ErrorCode::Unauthorized.as_str()),
+            Self::Forbidden(_) => Some(ErrorCode::Forbidden.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The payload of the error as rendered by [`DefaultErrorBody`] (unless
+    /// the problem details mode is enabled), as a [`ValidationErrorPayload`]
+    /// with the field errors, the errors and the details of the error if
+    /// any, e.g. to report the errors of the items of a
+    /// [`BatchResult`](crate::page::BatchResult). The message of the
+    /// internal errors is not exposed.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::{AppError, ValidationErrorPayload};
+    /// use actix_web::http::StatusCode;
+    /// use serde_json::json;
+    ///
+    /// let error = AppError::Custom(
+    ///     StatusCode::BAD_REQUEST,
+    ///     ValidationErrorPayload::with_code("validation_error".to_owned(), "Validation error".to_owned())
+    ///         .field("email", "email", "Invalid email", json!({ "value": "jhon" })),
+    /// );
+    /// let payload = error.payload();
+    /// assert_eq!(payload.field_errors.unwrap()["email"][0].code, "email");
+    ///
+    /// let payload = AppError::Unexpected(anyhow::anyhow!("Connection refused")).payload();
+    /// assert_eq!(payload.error, "Internal Server Error");
+    /// ```
+    pub fn payload(&self) -> ValidationErrorPayload {
+        match self {
+            Self::WithHeaders(error, _) => error.payload(),
+            Self::StaticValidation(detail) => ValidationErrorPayload::new(detail.to_string()),
+            Self::Unavailable(detail, _) => {
+                ValidationErrorPayload::with_code(ErrorCode::Unavailable.to_string(), detail.to_string())
+            }
+            _ => self.validation_payload().unwrap_or_else(|| {
+                let message = self.status_code().canonical_reason().unwrap_or("Unknown error").to_owned();
+                match self.code() {
+                    Some(code) => ValidationErrorPayload::with_code(code.to_owned(), message),
+                    None => ValidationErrorPayload::new(message),
+                }
+            }),
+        }
+    }
+
+    /// The payload of the errors rendered as [`ValidationErrorPayload`] by
+    /// [`DefaultErrorBody`], or `None` for the ones rendered
+    /// as [`InternalErrorPayload`].
+    fn validation_payload(&self) -> Option<ValidationErrorPayload> {
+        let payload = match self {
+            Self::WithHeaders(error, _) => return error.validation_payload(),
+            Self::Validation(code, detail) => match code {
+                None => ValidationErrorPayload::new(detail.to_owned()),
+                Some(c) => ValidationErrorPayload::with_code(c.to_string(), detail.to_owned()),
+            },
+            Self::Validations(errors) => {
+                let mut payload = ValidationErrorPayload::with_code(
+                    ErrorCode::ValidationError.to_string(),
+                    if errors.len() > 1 { "Validations error".to_owned() } else { "Validation error".to_owned() },
+                );
+                payload.errors = Some(
+                    errors
+                        .iter()
+                        .map(|(code, error)| ErrorMessagePayload {
+                            code: code.map(String::from),
+                            error: error.to_owned(),
+                        })
+                        .collect(),
+                );
+                payload
+            }
+            Self::Custom(_, payload) => payload.clone(),
+            Self::ResourceNotFoundBy { resource, attributes } => ValidationErrorPayload::with_details(
+                ErrorCode::NotFound.to_string(),
+                self.to_string(),
+                serde_json::json!({
+                    "resource": resource,
+                    "attributes": attributes.iter().cloned().collect::<HashMap<_, _>>(),
+                }),
+            ),
+            Self::Upstream { service, status, source: _ } => ValidationErrorPayload::with_details(
+                ErrorCode::UpstreamError.to_string(),
+                self.to_string(),
+                serde_json::json!({ "service": service, "status": status.map(|s| s.as_u16()) }),
+            ),
+            Self::PreconditionFailed { resource, expected, actual } => ValidationErrorPayload::with_details(
+                ErrorCode::StaleVersion.to_string(),
+                self.to_string(),
+                serde_json::json!({ "resource": resource, "expected": expected, "actual": actual }),
+            ),
+            Self::PayloadTooLarge { limit } => ValidationErrorPayload::with_details(
+                ErrorCode::PayloadTooLarge.to_string(),
+                self.to_string(),
+                serde_json::json!({ "limit": limit }),
+            ),
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_UNIQUE_VIOLATION) => self.unique_violation_payload(),
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_FOREIGN_KEY_VIOLATION) => self.foreign_key_violation_payload(),
+            Self::ResourceNotFound { resource: _, attribute: _, value: _ }
+                | Self::ResourceAlreadyExists { resource: _, attribute: _, value: _ }
+                | Self::Conflict { resource: _, attribute: _, value: _ }
+                | Self::Gone { resource: _, value: _ }
+                | Self::TooManyRequests { retry_after: _, message: _ }
+                | Self::Unauthorized(_) | Self::Forbidden(_) => ValidationErrorPayload::with_code(
+                    self.code().unwrap_or_default().to_string(),
+                    self.to_string(),
+                ),
+            _ => return None,
+        };
+        Some(payload)
+    }
+
+    /// Build a function to map a `SqlxError` into an [`AppError`], that
+    /// maps `SqlxError::RowNotFound` into [`AppError::ResourceNotFound`]
+    /// (`HTTP 404 Not Found`) with the resource, attribute and value
+    /// passed, and any other error into [`AppError::DB`].
+    ///
+    /// Only available when the `sqlx` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// let customer = sqlx::query_as!(
+    ///     Customer,
+    ///     "SELECT id, name, created_at FROM customers WHERE id = $1", id
+    /// )
+    /// .fetch_one(&mut **tx)
+    /// .await
+    /// .map_err(AppError::db_not_found("customer", "id", id.to_string()))?;
+    /// ```
+    #[cfg(feature = "sqlx")]
+    pub fn db_not_found(
+        resource: &'static str,
+        attribute: &'static str,
+        value: String,
+    ) -> impl FnOnce(SqlxError) -> AppError {
+        move |err| match err {
+            SqlxError::RowNotFound => AppError::ResourceNotFound { resource, attribute, value },
+            _ => AppError::DB(err),
+        }
+    }
+
+    /// Build a function to map the errors sending requests with the `awc`
+    /// client into [`AppError::Upstream`] errors of the service passed,
+    /// with the `504 Gateway Timeout` status if the request timed out.
+    ///
+    /// Only available when the `awc-errors` feature is activated.
+    ///
+    /// # Example
+    /// ```ignore, no_run
+    /// use actix_contrib_rest::result::AppError;
+    /// // ...
+    /// let res = client
+    ///     .get(format!("{billing_url}/invoices/{id}"))
+    ///     .send()
+    ///     .await
+    ///     .map_err(AppError::upstream("billing"))?;
+    /// ```
+    #[cfg(feature = "awc-errors")]
+    pub fn upstream(service: &'static str) -> impl FnOnce(awc::error::SendRequestError) -> AppError {
+        move |err| send_request_error(service, err)
+    }
+
+    /// The parameters of the error message, used to render the
+    /// translated messages (see [`MessageCatalog`]), e.g. `resource`,
+    /// `attribute` and `value` for [`AppError::ResourceNotFound`].
+    pub fn message_params(&self) -> HashMap<Cow<'static, str>, Value> {
+        match self {
+            Self::WithHeaders(error, _) => error.message_params(),
+            Self::ResourceNotFound { resource, attribute, value }
+                | Self::ResourceAlreadyExists { resource, attribute, value }
+                | Self::Conflict { resource, attribute, value } => HashMap::from([
+                    (Cow::from("resource"), Value::from(*resource)),
+                    (Cow::from("attribute"), Value::from(*attribute)),
+                    (Cow::from("value"), Value::from(value.as_str())),
+                ]),
+            Self::ResourceNotFoundBy { resource, attributes } => {
+                let mut params = HashMap::from([(Cow::from("resource"), Value::from(*resource))]);
+                params.extend(
+                    attributes
+                        .iter()
+                        .map(|(attribute, value)| (Cow::from(*attribute), Value::from(value.as_str()))),
+                );
+                params
+            }
+            Self::Gone { resource, value } => HashMap::from([
+                (Cow::from("resource"), Value::from(*resource)),
+                (Cow::from("value"), Value::from(value.as_str())),
+            ]),
+            Self::PayloadTooLarge { limit } => HashMap::from([(Cow::from("limit"), Value::from(*limit))]),
+            Self::PreconditionFailed { resource, expected, actual } => HashMap::from([
+                (Cow::from("resource"), Value::from(*resource)),
+                (Cow::from("expected"), Value::from(expected.as_str())),
+                (Cow::from("actual"), Value::from(actual.as_str())),
+            ]),
+            Self::Upstream { service, status: _, source: _ } => HashMap::from([
+                (Cow::from("service"), Value::from(*service)),
+            ]),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// The error message translated into the locale passed, using the catalog
+    /// set with [`set_message_catalog()`], or `None` if there is no
+    /// translation for the error code.
+    pub fn localized_message(&self, locale: &str) -> Option<String> {
+        let code = self.code()?;
+        with_message_catalog(|catalog| catalog.message(locale, code, &self.message_params())).flatten()
+    }
+
+    /// Add a header to the response of the error, wrapping the error into
+    /// [`AppError::WithHeaders`] if it's not already wrapped. Invalid headers
+    /// are ignored with a warning.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::AppError;
+    /// use actix_web::ResponseError;
+    ///
+    /// let error = AppError::Forbidden("The plan doesn't include exports".to_owned())
+    ///     .with_header(("X-Error-Code", "plan_limit"))
+    ///     .with_header(("Deprecation", "true"));
+    /// let res = error.error_response();
+    /// assert_eq!(res.status(), 403);
+    /// assert_eq!(res.headers().get("x-error-code").unwrap(), "plan_limit");
+    /// assert_eq!(res.headers().get("deprecation").unwrap(), "true");
+    /// ```
+    pub fn with_header(self, header: impl TryIntoHeaderPair) -> Self {
+        let (error, mut headers) = match self {
+            Self::WithHeaders(error, headers) => (error, headers),
+            error => (Box::new(error), Vec::new()),
+        };
+        match header.try_into_pair() {
+            Ok(pair) => headers.push(pair),
+            Err(e) => warn!("Invalid header for the error response: {}", Into::<HttpError>::into(e)),
+        }
+        Self::WithHeaders(error, headers)
+    }
+
+    /// The database error, if the error is an `AppError::DB` error.
+    ///
+    /// Only available when the `sqlx` feature is activated.
+    #[cfg(feature = "sqlx")]
+    pub fn as_db(&self) -> Option<&SqlxError> {
+        match self {
+            Self::DB(e) => Some(e),
+            Self::WithHeaders(error, _) => error.as_db(),
+            _ => None,
+        }
+    }
+
+    /// The code (if any) and the message of the error, if the error is
+    /// an [`AppError::Validation`] or [`AppError::StaticValidation`] error.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::AppError;
+    ///
+    /// let error = AppError::Validation(Some("insufficient_funds"), "Not enough funds.".to_owned());
+    /// assert_eq!(error.as_validation(), Some((Some("insufficient_funds"), "Not enough funds.")));
+    /// ```
+    pub fn as_validation(&self) -> Option<(Option<&'static str>, &str)> {
+        match self {
+            Self::Validation(code, message) => Some((*code, message)),
+            Self::StaticValidation(message) => Some((None, message)),
+            Self::WithHeaders(error, _) => error.as_validation(),
+            _ => None,
+        }
+    }
+
+    /// The first error of type `E` wrapped by the error, looking into the
+    /// [`AppError::Unexpected`] errors and the sources of the other errors,
+    /// so service functions returning [`Result`] can be reused in
+    /// non-HTTP layers (e.g. CLI jobs) branching on the concrete error.
+    ///
+    /// ```
+    /// use actix_contrib_rest::result::AppError;
+    /// use std::io;
+    ///
+    /// let error = AppError::from(io::Error::new(io::ErrorKind::TimedOut, "Timed out"));
+    /// assert_eq!(error.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::TimedOut);
+    /// ```
+    pub fn downcast_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            Self::Unexpected(e) => e.chain().find_map(|e| e.downcast_ref::<E>()),
+            Self::WithHeaders(error, _) => error.downcast_ref(),
+            _ => std::iter::successors(std::error::Error::source(self), |e| e.source())
+                .find_map(|e| e.downcast_ref::<E>()),
+        }
+    }
+
+    /// The messages of the error and all its sources.
+    pub fn chain(&self) -> Vec<String> {
+        match self {
+            Self::WithHeaders(error, _) => error.chain(),
+            _ => std::iter::successors(Some(self as &dyn std::error::Error), |e| e.source())
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+
+    /// Whether the error is an internal error (like [`AppError::Unexpected`]),
+    /// whose details should not be exposed to the client.
+    pub fn is_internal(&self) -> bool {
+        match self {
+            Self::Unexpected(_) => true,
+            Self::WithHeaders(error, _) => error.is_internal(),
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_UNIQUE_VIOLATION) => false,
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_FOREIGN_KEY_VIOLATION) => false,
+            #[cfg(feature = "sqlx")]
+            Self::DB(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the request that failed can be retried, e.g. when the service
+    /// is unavailable, or the database timed out or aborted the transaction
+    /// because of a deadlock or a serialization failure.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::TooManyRequests { retry_after: _, message: _ } => true,
+            Self::WithHeaders(error, _) => error.is_retryable(),
+            Self::Unavailable(_, _) => true,
+            Self::Upstream { service: _, status: _, source: _ } => true,
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_SERIALIZATION_FAILURE) => true,
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_DEADLOCK_DETECTED) => true,
+            #[cfg(feature = "sqlx")]
+            Self::DB(SqlxError::PoolTimedOut | SqlxError::Io(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// The time the client should wait before making a new request,
+    /// sent in the `Retry-After` header, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::TooManyRequests { retry_after, message: _ } => *retry_after,
+            Self::Unavailable(_, retry_after) => *retry_after,
+            Self::WithHeaders(error, _) => error.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// The error raised by the database, if any.
+    #[cfg(feature = "sqlx-postgres")]
+    fn db_error(&self) -> Option<&(dyn DatabaseError + 'static)> {
+        match self {
+            Self::DB(SqlxError::Database(e)) => Some(&**e),
+            _ => None,
+        }
+    }
+
+    /// Whether the error is a database error with the SQLSTATE code passed.
+    #[cfg(feature = "sqlx-postgres")]
+    fn is_db_violation(&self, sqlstate: &str) -> bool {
+        self.db_error().and_then(|e| e.code()).is_some_and(|c| c == sqlstate)
+    }
+
+    /// Payload of a unique constraint violation, with the field
+    /// registered for the constraint (if any) in the `field_errors`.
+    #[cfg(feature = "sqlx-postgres")]
+    fn unique_violation_payload(&self) -> ValidationErrorPayload {
+        let mut payload = ValidationErrorPayload::with_code(
+            ErrorCode::Conflict.to_string(),
+            "Resource already exists".to_owned(),
+        );
+        if let Some(constraint) = self.db_error().and_then(|e| e.constraint()) {
+            if let Some(field) = constraint_field(constraint) {
+                let mut error = ValidationError::new(ErrorCode::Unique.as_str());
+                error.add_param(Cow::from("constraint"), &constraint);
+                payload.field_errors = Some(HashMap::from([(field, vec![error])]));
+            }
+        }
+        payload
+    }
+
+    /// Payload of a foreign key violation, with the column, the value
+    /// and the table of the reference in the `field_errors`. The field
+    /// reported is the one registered for the constraint with
+    /// [`register_constraint_field()`], or the column name otherwise.
+    #[cfg(feature = "sqlx-postgres")]
+    fn foreign_key_violation_payload(&self) -> ValidationErrorPayload {
+        let db_error = self.db_error();
+        let detail = db_error
+            .and_then(|e| e.try_downcast_ref::<PgDatabaseError>())
+            .and_then(|e| e.detail());
+        // The detail is "... is still referenced from table ..." when
+        // deleting or updating a row referenced by another table
+        let still_referenced = detail.is_some_and(|d| d.contains("is still referenced"));
+        let mut payload = ValidationErrorPayload::with_code(
+            ErrorCode::InvalidReference.to_string(),
+            if still_referenced {
+                "Resource is still referenced by other resources".to_owned()
+            } else {
+                "Referenced resource not found".to_owned()
+            },
+        );
+        if let Some((column, value, table)) = detail.and_then(parse_fk_detail) {
+            let field = db_error
+                .and_then(|e| e.constraint())
+                .and_then(constraint_field)
+                .unwrap_or_else(|| column.to_owned());
+            let mut error = ValidationError::new(ErrorCode::ForeignKey.as_str());
+            error.add_param(Cow::from("table"), &table);
+            error.add_param(Cow::from("column"), &column);
+            error.add_param(Cow::from("value"), &value);
+            payload.field_errors = Some(HashMap::from([(field, vec![error])]));
+        }
+        payload
+    }
+}
+
+/// The `anyhow` errors are wrapped into [`AppError::Unexpected`], unless the
+/// error is an [`AppError`] itself (e.g. one converted into an `anyhow::Error`
+/// by a non-HTTP layer), that is unwrapped as is.
+///
+/// ```
+/// use actix_contrib_rest::result::AppError;
+///
+/// let error = anyhow::Error::from(AppError::Forbidden("Not allowed".to_owned()));
+/// assert!(matches!(AppError::from(error), AppError::Forbidden(_)));
+/// ```
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<AppError>().unwrap_or_else(AppError::Unexpected)
+    }
+}
+
+/// IO errors are processed as `HTTP 500 Internal Server Error`,
+/// wrapped into [`AppError::Unexpected`].
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::Unexpected(error.into())
+    }
+}
+
+/// JSON (de)serialization errors are processed as `HTTP 422 Unprocessable Entity`,
+/// like the errors deserializing the request payloads.
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Custom(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ValidationErrorPayload::new(error.to_string()),
+        )
+    }
+}
+
+/// Error sending a request to the service passed with the `awc` client,
+/// with the `504 Gateway Timeout` status if the request timed out.
+pub(crate) fn send_request_error(service: &'static str, err: awc::error::SendRequestError) -> AppError {
+    let status = match err {
+        awc::error::SendRequestError::Timeout => Some(StatusCode::GATEWAY_TIMEOUT),
+        _ => None,
+    };
+    AppError::Upstream { service, status, source: error_chain(&err) }
+}
+
+/// Copy of the error passed and its chain of sources, for errors that
+/// cannot be wrapped with `anyhow` because they are not `Send + Sync`,
+/// like the `awc` errors. Each source is kept as a context layer, so
+/// the messages of the whole chain are still reported.
+pub(crate) fn error_chain(err: &dyn std::error::Error) -> anyhow::Error {
+    let mut messages = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        messages.push(err.to_string());
+        source = err.source();
+    }
+    let mut messages = messages.into_iter().rev();
+    let root = anyhow::Error::msg(messages.next().unwrap_or_default());
+    messages.fold(root, |error, message| error.context(message))
+}
+
+/// Errors sending requests to other services with the `awc` client are
+/// processed as [`AppError::Upstream`] errors of the `"upstream"` service:
+/// `HTTP 504 Gateway Timeout` if the request timed out, otherwise
+/// `HTTP 502 Bad Gateway`. Use [`AppError::upstream()`] to set
+/// the name of the service.
+///
+/// Only available when the `awc-errors` feature is activated.
+#[cfg(feature = "awc-errors")]
+impl From<awc::error::SendRequestError> for AppError {
+    fn from(error: awc::error::SendRequestError) -> Self {
+        AppError::upstream("upstream")(error)
+    }
 }
 
 impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::StaticValidation(_) | Self::Validation(_, _) => StatusCode::BAD_REQUEST,
+            Self::Validations(_) => StatusCode::BAD_REQUEST,
             Self::ResourceAlreadyExists { resource: _, attribute: _, value: _ } => StatusCode::BAD_REQUEST,
             Self::ResourceNotFound { resource: _, attribute: _, value: _ } => StatusCode::NOT_FOUND,
+            Self::ResourceNotFoundBy { resource: _, attributes: _ } => StatusCode::NOT_FOUND,
+            Self::Conflict { resource: _, attribute: _, value: _ } => StatusCode::CONFLICT,
+            Self::Gone { resource: _, value: _ } => StatusCode::GONE,
+            Self::PayloadTooLarge { limit: _ } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::PreconditionFailed { resource: _, expected: _, actual: _ } => StatusCode::PRECONDITION_FAILED,
+            Self::TooManyRequests { retry_after: _, message: _ } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Unavailable(_, _) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Upstream { service: _, status: Some(StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT), source: _ } => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            Self::Upstream { service: _, status: _, source: _ } => StatusCode::BAD_GATEWAY,
             Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Custom(status, _) => *status,
+            Self::WithHeaders(error, _) => error.status_code(),
             Self::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_UNIQUE_VIOLATION) => StatusCode::CONFLICT,
+            #[cfg(feature = "sqlx-postgres")]
+            Self::DB(_) if self.is_db_violation(PG_FOREIGN_KEY_VIOLATION) => StatusCode::BAD_REQUEST,
             #[cfg(feature = "sqlx")]
             Self::DB(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
+        self.build_response(ErrorFormat::Json, &ErrorContext::default())
+    }
+}
+
+/// Formats in which the error responses can be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Json,
+    Text,
+    Xml,
+}
+
+impl ErrorFormat {
+    /// Pick the format from the `Accept` header of the request,
+    /// JSON if the header is not set or none of the formats is accepted.
+    fn from_request(req: &HttpRequest) -> Self {
+        let accept = Accept::parse(req).map(|a| a.ranked()).unwrap_or_default();
+        accept
+            .iter()
+            .find_map(|mime| match mime.essence_str() {
+                "application/json" | "application/problem+json" | "application/*" | "*/*" => {
+                    Some(Self::Json)
+                }
+                "text/plain" | "text/*" => Some(Self::Text),
+                "application/xml" | "text/xml" => Some(Self::Xml),
+                _ => None,
+            })
+            .unwrap_or(Self::Json)
+    }
+}
+
+impl AppError {
+    /// Build the response of the error as [`ResponseError::error_response()`]
+    /// does, but honoring the `Accept` header of the request: JSON by default,
+    /// or the same payload rendered as `text/plain` or `application/xml`
+    /// if requested by the client. The payload also includes the
+    /// context of the request, like the request ID (see [`ErrorContext`]).
+    ///
+    /// Use [`app_error_handler`](crate::response::app_error_handler) to render
+    /// the errors returned by the handlers with this method.
+    pub fn response_for(&self, req: &HttpRequest) -> HttpResponse {
+        self.build_response(ErrorFormat::from_request(req), &ErrorContext::from_request(req))
+    }
+
+    fn build_response(&self, format: ErrorFormat, ctx: &ErrorContext) -> HttpResponse {
+        if let Self::WithHeaders(error, headers) = self {
+            let mut res = error.build_response(format, ctx);
+            for (name, value) in headers {
+                res.headers_mut().insert(name.clone(), value.clone());
+            }
+            return res;
+        }
+        if self.is_internal() {
+            report_error(self, ctx);
+        }
+        record_error_metrics(self, ctx);
         let status_code = self.status_code();
-        match self {
-            Self::Validation(code, error) => {
-                match code {
-                    None => HttpResponse::build(status_code)
-                        .json(ValidationErrorPayload::new(error.to_owned())),
-                    Some(c) =>
-                        HttpResponse::build(status_code)
-                            .json(ValidationErrorPayload::with_code(c.to_string(), error.to_owned())),
+        if status_code.is_server_error() {
+            match read_setting(&SERVER_ERROR_HOOK).as_deref() {
+                Some(hook) => hook(self, ctx),
+                None => log_server_error(self, ctx),
+            }
+        }
+        let mut res = HttpResponse::build(status_code);
+        if let Some(retry_after) = self.retry_after() {
+            res.insert_header((RETRY_AFTER, retry_after.as_secs().to_string()));
+        }
+        if status_code == StatusCode::UNAUTHORIZED {
+            if let Some(challenge) = read_setting(&WWW_AUTHENTICATE_CHALLENGE).as_deref() {
+                res.insert_header((WWW_AUTHENTICATE, challenge));
+            }
+        }
+        with_error_body(|body| {
+            let payload = body.body(self, ctx);
+            match format {
+                ErrorFormat::Json => res.content_type(body.content_type()).json(payload),
+                ErrorFormat::Text => res
+                    .content_type("text/plain; charset=utf-8")
+                    .body(value_to_text(&payload)),
+                ErrorFormat::Xml => res
+                    .content_type("application/xml; charset=utf-8")
+                    .body(value_to_xml(&payload)),
+            }
+        })
+    }
+}
+
+static WWW_AUTHENTICATE_CHALLENGE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the challenge sent in the `WWW-Authenticate` header of the
+/// `HTTP 401 Unauthorized` responses, e.g. `Bearer realm="example"`,
+/// as required by RFC 6750 compliant clients. If not set,
+/// the header is not sent.
+///
+/// ```
+/// use actix_contrib_rest::result::set_www_authenticate;
+/// set_www_authenticate(r#"Bearer realm="api""#);
+/// ```
+pub fn set_www_authenticate(challenge: impl Into<String>) {
+    *write_setting(&WWW_AUTHENTICATE_CHALLENGE) = Some(challenge.into());
+}
+
+type ServerErrorHook = dyn Fn(&AppError, &ErrorContext) + Send + Sync;
+
+static SERVER_ERROR_HOOK: RwLock<Option<Box<ServerErrorHook>>> = RwLock::new(None);
+
+/// Set the hook called each time a server error (`HTTP 5xx`) response is
+/// built, with the error and the context of the request (empty if not
+/// available), by default [`log_server_error()`].
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{log_server_error, set_server_error_hook};
+///
+/// set_server_error_hook(|error, ctx| {
+///     // ... e.g. count the errors, and log them as well
+///     log_server_error(error, ctx);
+/// });
+/// ```
+pub fn set_server_error_hook(hook: impl Fn(&AppError, &ErrorContext) + Send + Sync + 'static) {
+    *write_setting(&SERVER_ERROR_HOOK) = Some(Box::new(hook));
+}
+
+/// Default hook for server errors (see [`set_server_error_hook()`]), that logs
+/// the status code, the path of the request and the chain of errors
+/// with `log::error!`.
+pub fn log_server_error(error: &AppError, ctx: &ErrorContext) {
+    error!(
+        "{} {}: {}",
+        error.status_code(),
+        ctx.path.as_deref().unwrap_or("-"),
+        error.chain().join(": ")
+    );
+}
+
+/// Hook for server errors (see [`set_server_error_hook()`]), that emits
+/// a `tracing` error event with the status code, the path and the ID
+/// of the request, and the chain of errors as fields.
+///
+/// Only available when the `tracing` feature is activated.
+#[cfg(feature = "tracing")]
+pub fn trace_server_error(error: &AppError, ctx: &ErrorContext) {
+    tracing::error!(
+        status = error.status_code().as_u16(),
+        path = ctx.path.as_deref().unwrap_or("-"),
+        request_id = ctx.request_id.as_deref().unwrap_or("-"),
+        chain = %error.chain().join(": "),
+        "{}",
+        error
+    );
+}
+
+/// Trait to report the internal errors ([`AppError::Unexpected`] and `AppError::DB`)
+/// to an external service, like Sentry or Honeybadger, before the response is built.
+///
+/// The reporter is set at startup with [`set_error_reporter()`], and it's
+/// also implemented for closures.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{set_error_reporter, AppError, ErrorContext, ErrorReporter};
+///
+/// struct SentryReporter;
+///
+/// impl ErrorReporter for SentryReporter {
+///     fn report(&self, error: &AppError, ctx: &ErrorContext) {
+///         // sentry::capture_error(error) ...
+///     }
+/// }
+///
+/// set_error_reporter(SentryReporter);
+/// ```
+pub trait ErrorReporter: Send + Sync {
+    /// Report the error, with the context of the request (empty if not available).
+    fn report(&self, error: &AppError, ctx: &ErrorContext);
+}
+
+impl<F> ErrorReporter for F
+where
+    F: Fn(&AppError, &ErrorContext) + Send + Sync,
+{
+    fn report(&self, error: &AppError, ctx: &ErrorContext) {
+        self(error, ctx)
+    }
+}
+
+static ERROR_REPORTER: RwLock<Option<Box<dyn ErrorReporter>>> = RwLock::new(None);
+
+/// Set the [`ErrorReporter`] used to report the internal errors.
+/// If not set, the errors are not reported.
+pub fn set_error_reporter(reporter: impl ErrorReporter + 'static) {
+    *write_setting(&ERROR_REPORTER) = Some(Box::new(reporter));
+}
+
+/// Report the error with the [`ErrorReporter`] set, if any. It's called
+/// automatically when the response of an internal error is built.
+pub fn report_error(error: &AppError, ctx: &ErrorContext) {
+    if let Some(reporter) = read_setting(&ERROR_REPORTER).as_deref() {
+        reporter.report(error, ctx);
+    }
+}
+
+/// Trait to record metrics of the errors rendered, e.g. counters
+/// by status code, error code and the route of the handler.
+///
+/// The metrics recorder is set at startup with [`set_error_metrics()`], and
+/// it's also implemented for closures. A ready-made implementation for
+/// Prometheus is available with the `metrics` feature, see
+/// [`PrometheusErrorMetrics`].
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{set_error_metrics, AppError, ErrorContext};
+///
+/// set_error_metrics(|error: &AppError, ctx: &ErrorContext| {
+///     // ... e.g. statsd.incr(&format!("errors.{}", error.status_code().as_u16()))
+/// });
+/// ```
+pub trait ErrorMetrics: Send + Sync {
+    /// Record the error, with the context of the request (empty if not available).
+    fn record(&self, error: &AppError, ctx: &ErrorContext);
+}
+
+impl<F> ErrorMetrics for F
+where
+    F: Fn(&AppError, &ErrorContext) + Send + Sync,
+{
+    fn record(&self, error: &AppError, ctx: &ErrorContext) {
+        self(error, ctx)
+    }
+}
+
+static ERROR_METRICS: RwLock<Option<Box<dyn ErrorMetrics>>> = RwLock::new(None);
+
+/// Set the [`ErrorMetrics`] used to record the metrics of all
+/// the errors rendered. If not set, no metrics are recorded.
+pub fn set_error_metrics(metrics: impl ErrorMetrics + 'static) {
+    *write_setting(&ERROR_METRICS) = Some(Box::new(metrics));
+}
+
+/// Record the error with the [`ErrorMetrics`] set, if any. It's called
+/// automatically each time the response of an error is built.
+pub fn record_error_metrics(error: &AppError, ctx: &ErrorContext) {
+    if let Some(metrics) = read_setting(&ERROR_METRICS).as_deref() {
+        metrics.record(error, ctx);
+    }
+}
+
+/// [`ErrorMetrics`] implementation that counts the errors with the
+/// `http_errors_total` Prometheus counter, labeled with the `status` code,
+/// the error `code`, and the `route` of the handler (`"-"` if unknown).
+///
+/// Only available when the `metrics` feature is activated.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{set_error_metrics, PrometheusErrorMetrics};
+/// use prometheus::Registry;
+///
+/// let registry = Registry::new();
+/// set_error_metrics(PrometheusErrorMetrics::new(&registry).unwrap());
+/// ```
+#[cfg(feature = "metrics")]
+#[derive(Clone)]
+pub struct PrometheusErrorMetrics {
+    counter: prometheus::IntCounterVec,
+}
+
+#[cfg(feature = "metrics")]
+impl PrometheusErrorMetrics {
+    /// Create the counter and register it in the registry passed.
+    pub fn new(registry: &prometheus::Registry) -> prometheus::Result<Self> {
+        let counter = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("http_errors_total", "Number of error responses"),
+            &["status", "code", "route"],
+        )?;
+        registry.register(Box::new(counter.clone()))?;
+        Ok(PrometheusErrorMetrics { counter })
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ErrorMetrics for PrometheusErrorMetrics {
+    fn record(&self, error: &AppError, ctx: &ErrorContext) {
+        self.counter
+            .with_label_values(&[
+                error.status_code().as_str(),
+                error.code().unwrap_or("-"),
+                ctx.route.as_deref().unwrap_or("-"),
+            ])
+            .inc();
+    }
+}
+
+/// Render the payload as plain text, one `key: value` line
+/// for each value, with the nested keys joined by dots.
+fn value_to_text(payload: &Value) -> String {
+    fn write(out: &mut String, path: &str, value: &Value) {
+        match value {
+            Value::Null => {}
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let path = if path.is_empty() { key.to_owned() } else { format!("{path}.{key}") };
+                    write(out, &path, value);
                 }
             }
-            Self::StaticValidation(error)
-                | Self::Unauthorized(error) | Self::Forbidden(error) => {
-                HttpResponse::build(status_code)
-                    .json(InternalErrorPayload::init(error))
+            Value::Array(items) => {
+                for (i, value) in items.iter().enumerate() {
+                    write(out, &format!("{path}.{i}"), value);
+                }
             }
-            Self::ResourceNotFound { resource: _, attribute: _, value: _ } => {
-                HttpResponse::build(status_code)
-                    .json(ValidationErrorPayload::with_code(
-                        "not_found".to_string(),
-                        self.to_string(),
-                    ))
+            Value::String(s) if path.is_empty() => out.push_str(&format!("{s}\n")),
+            Value::String(s) => out.push_str(&format!("{path}: {s}\n")),
+            _ => out.push_str(&format!("{path}: {value}\n")),
+        }
+    }
+    let mut out = String::new();
+    write(&mut out, "", payload);
+    out
+}
+
+/// Render the payload as a XML document with an `<error>` root element,
+/// the arrays are rendered repeating the element of the array key.
+/// The keys that are not valid XML names, e.g. field paths like
+/// `items[2].qty`, are rendered as `<entry name="items[2].qty">`.
+fn value_to_xml(payload: &Value) -> String {
+    fn write(out: &mut String, key: &str, value: &Value) {
+        let (open, close) = if is_xml_name(key) {
+            (key.to_owned(), key)
+        } else {
+            (format!("entry name=\"{}\"", escape_xml(key)), "entry")
+        };
+        match value {
+            Value::Null => {}
+            Value::Array(items) => items.iter().for_each(|item| write(out, key, item)),
+            Value::Object(map) => {
+                out.push_str(&format!("<{open}>"));
+                map.iter().for_each(|(key, value)| write(out, key, value));
+                out.push_str(&format!("</{close}>"));
             }
-            Self::ResourceAlreadyExists { resource: _, attribute: _, value: _ } => {
-                HttpResponse::build(status_code)
-                    .json(ValidationErrorPayload::with_code(
-                        "already_exists".to_string(),
-                        self.to_string(),
-                    ))
+            Value::String(s) => out.push_str(&format!("<{open}>{}</{close}>", escape_xml(s))),
+            _ => out.push_str(&format!("<{open}>{value}</{close}>")),
+        }
+    }
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    write(&mut out, "error", payload);
+    out
+}
+
+/// Whether the name passed can be used as is as a XML element name, only
+/// allowing ASCII names that don't start with the reserved `xml` prefix.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid_start = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("xml"))
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Trait to customize how [`AppError`] errors are serialized in
+/// the response body, e.g. to follow a mandatory error envelope.
+///
+/// The default implementation is [`DefaultErrorBody`], that can be
+/// replaced at startup with [`set_error_body()`].
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{set_error_body, AppError, ErrorBody, ErrorContext};
+/// use actix_web::ResponseError;
+/// use serde_json::{json, Value};
+///
+/// struct EnvelopeErrorBody;
+///
+/// impl ErrorBody for EnvelopeErrorBody {
+///     fn body(&self, error: &AppError, ctx: &ErrorContext) -> Value {
+///         json!({
+///             "errors": [{
+///                 "status": error.status_code().as_u16(),
+///                 "code": error.code(),
+///                 "request_id": ctx.request_id,
+///             }]
+///         })
+///     }
+/// }
+///
+/// set_error_body(EnvelopeErrorBody);
+/// ```
+pub trait ErrorBody: Send + Sync {
+    /// Build the body of the response for the error passed, with the
+    /// context of the request (empty if not available).
+    fn body(&self, error: &AppError, ctx: &ErrorContext) -> Value;
+
+    /// The content type of the response body, `application/json` by default.
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+}
+
+/// Default [`ErrorBody`] implementation, that serializes the errors
+/// as [`InternalErrorPayload`] or [`ValidationErrorPayload`] depending
+/// on the error, or as [`ProblemDetailsPayload`] if the
+/// mode is enabled with [`set_problem_details()`].
+///
+/// The payloads of the server errors (`HTTP 5xx`) include whether the
+/// request can be retried, see [`AppError::is_retryable()`].
+///
+/// ```
+/// use actix_contrib_rest::result::{AppError, DefaultErrorBody, ErrorBody, ErrorContext};
+///
+/// let error = AppError::Upstream { service: "billing", status: None, source: anyhow::anyhow!("Connection reset") };
+/// let body = DefaultErrorBody.body(&error, &ErrorContext::default());
+/// assert_eq!(body["code"], "upstream_error");
+/// assert_eq!(body["retryable"], true);
+///
+/// let body = DefaultErrorBody.body(&AppError::Forbidden("No access".to_owned()), &ErrorContext::default());
+/// assert!(body.get("retryable").is_none());
+/// ```
+pub struct DefaultErrorBody;
+
+impl ErrorBody for DefaultErrorBody {
+    /// Build the payload of the error, with the field names
+    /// renamed with the casing set with [`set_field_casing()`].
+    fn body(&self, error: &AppError, ctx: &ErrorContext) -> Value {
+        let mut payload = self.payload(error, ctx);
+        apply_field_casing(&mut payload);
+        payload
+    }
+
+    fn content_type(&self) -> &'static str {
+        if problem_details() { "application/problem+json" } else { "application/json" }
+    }
+}
+
+impl DefaultErrorBody {
+    fn payload(&self, error: &AppError, ctx: &ErrorContext) -> Value {
+        let message = ctx.locale.as_deref().and_then(|locale| error.localized_message(locale));
+        if problem_details() {
+            let mut payload = ProblemDetailsPayload::from(error);
+            payload.instance = ctx.path.clone();
+            payload.request_id = ctx.request_id.clone();
+            if payload.detail.is_some() && message.is_some() {
+                payload.detail = message;
+            }
+            return to_value(payload);
+        }
+        let mut payload = match error.validation_payload() {
+            Some(payload) => to_value(payload),
+            None => match error {
+                AppError::StaticValidation(detail) => to_value(InternalErrorPayload::init(detail)),
+                AppError::Unavailable(detail, _) => {
+                    to_value(InternalErrorPayload {
+                        code: Some(Cow::Borrowed(ErrorCode::Unavailable.as_str())),
+                        error: Cow::Borrowed(*detail),
+                        retryable: None,
+                        request_id: None,
+                        debug: None,
+                    })
+                }
+                _ => {
+                    let mut payload = InternalErrorPayload::init(
+                        error.status_code().canonical_reason().unwrap_or("Unknown error")
+                    );
+                    if debug() {
+                        payload.debug = Some(ErrorDebugPayload::from(error));
+                    }
+                    to_value(payload)
+                }
+            },
+        };
+        if let Value::Object(map) = &mut payload {
+            if error.status_code().is_server_error() {
+                map.insert("retryable".to_owned(), Value::from(error.is_retryable()));
             }
-            _ => {
-                HttpResponse::build(status_code)
-                    .json(InternalErrorPayload::init(
-                        status_code.canonical_reason().unwrap_or("Unknown error")
-                    ))
+            if let Some(message) = message {
+                map.insert("error".to_owned(), Value::from(message));
+            }
+            if let Some(request_id) = &ctx.request_id {
+                map.insert("request_id".to_owned(), Value::from(request_id.as_str()));
             }
         }
+        payload
+    }
+}
+
+static ERROR_BODY: RwLock<Option<Box<dyn ErrorBody>>> = RwLock::new(None);
+
+/// Replace the [`DefaultErrorBody`] implementation used to serialize
+/// the [`AppError`] errors with the one passed.
+pub fn set_error_body(body: impl ErrorBody + 'static) {
+    *write_setting(&ERROR_BODY) = Some(Box::new(body));
+}
+
+fn with_error_body<R>(f: impl FnOnce(&dyn ErrorBody) -> R) -> R {
+    match read_setting(&ERROR_BODY).as_deref() {
+        Some(body) => f(body),
+        None => f(&DefaultErrorBody),
+    }
+}
+
+fn to_value(payload: impl Serialize) -> Value {
+    serde_json::to_value(payload).unwrap_or_default()
+}
+
+/// Trait to translate the error messages into the language of the
+/// client, keyed by the error code, e.g. `"not_found"` for
+/// [`AppError::ResourceNotFound`] errors, or `"length"`
+/// for field validation errors.
+///
+/// The locale is taken from the `Accept-Language` header (see [`Locale`]),
+/// and the catalog is set at startup with [`set_message_catalog()`].
+/// See [`TemplateCatalog`] for a simple implementation.
+pub trait MessageCatalog: Send + Sync {
+    /// The message for the locale and the error code passed, rendered with
+    /// the params of the error, or `None` if there is no translation.
+    fn message(
+        &self,
+        locale: &str,
+        code: &str,
+        params: &HashMap<Cow<'static, str>, Value>,
+    ) -> Option<String>;
+}
+
+/// [`MessageCatalog`] implementation with message templates registered by
+/// locale and error code, where the `{param}` placeholders are replaced by
+/// the params of the error. If there is no template for a regional
+/// locale like `es-AR`, the template of the language (`es`) is used.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::{set_message_catalog, AppError, TemplateCatalog};
+///
+/// set_message_catalog(
+///     TemplateCatalog::new()
+///         .add("es", "not_found", "No se encontró {resource} con {attribute} igual a \"{value}\"")
+///         .add("es", "length", "Longitud inválida")
+/// );
+/// let error = AppError::ResourceNotFound { resource: "order", attribute: "id", value: "12".to_owned() };
+/// assert_eq!(
+///     error.localized_message("es-AR").unwrap(),
+///     "No se encontró order con id igual a \"12\""
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateCatalog {
+    templates: HashMap<(String, String), String>,
+}
+
+impl TemplateCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the template for the locale and the error code passed.
+    pub fn add(mut self, locale: &str, code: &str, template: &str) -> Self {
+        self.templates.insert((locale.to_owned(), code.to_owned()), template.to_owned());
+        self
+    }
+}
+
+impl MessageCatalog for TemplateCatalog {
+    fn message(
+        &self,
+        locale: &str,
+        code: &str,
+        params: &HashMap<Cow<'static, str>, Value>,
+    ) -> Option<String> {
+        let language = locale.split('-').next().unwrap_or(locale);
+        let template = self
+            .templates
+            .get(&(locale.to_owned(), code.to_owned()))
+            .or_else(|| self.templates.get(&(language.to_owned(), code.to_owned())))?;
+        Some(params.iter().fold(template.to_owned(), |message, (name, value)| {
+            let value = match value {
+                Value::String(s) => s.to_owned(),
+                _ => value.to_string(),
+            };
+            message.replace(&format!("{{{name}}}"), &value)
+        }))
     }
 }
 
+static MESSAGE_CATALOG: RwLock<Option<Box<dyn MessageCatalog>>> = RwLock::new(None);
+
+/// Set the [`MessageCatalog`] used to translate the error messages.
+/// If not set, the messages are not translated.
+pub fn set_message_catalog(catalog: impl MessageCatalog + 'static) {
+    *write_setting(&MESSAGE_CATALOG) = Some(Box::new(catalog));
+}
+
+fn with_message_catalog<R>(f: impl FnOnce(&dyn MessageCatalog) -> R) -> Option<R> {
+    read_setting(&MESSAGE_CATALOG).as_deref().map(f)
+}
+
+/// The locale with the highest preference in the `Accept-Language`
+/// header of the request, if any.
+fn accept_language(req: &HttpRequest) -> Option<String> {
+    let header = req.headers().get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+    header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        // The first locale with the highest quality
+        .fold(None, |best: Option<(&str, f32)>, (tag, quality)| match best {
+            Some((_, q)) if q >= quality => best,
+            _ => Some((tag, quality)),
+        })
+        .map(|(tag, _)| tag.to_owned())
+}
+
+/// Extractor of the preferred locale of the client, taken from
+/// the `Accept-Language` header, or `"en"` if not set.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::result::Locale;
+/// use actix_web::{get, HttpResponse, Responder};
+///
+/// #[get("/greeting")]
+/// async fn greeting(locale: Locale) -> impl Responder {
+///     match locale.language() {
+///         "es" => HttpResponse::Ok().body("Hola"),
+///         _ => HttpResponse::Ok().body("Hello"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(pub String);
+
+impl Locale {
+    /// The language of the locale, e.g. `es` for `es-AR`.
+    pub fn language(&self) -> &str {
+        self.0.split('-').next().unwrap_or(&self.0)
+    }
+}
+
+impl FromRequest for Locale {
+    type Error = actix_web::Error;
+    type Future = Ready<core::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Locale(accept_language(req).unwrap_or_else(|| "en".to_owned()))))
+    }
+}
+
+/// Return early with an [`AppError::Validation`] error, with the
+/// code and the message formatted with the arguments passed.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::app_bail;
+/// use actix_contrib_rest::result::{AppError, Result};
+///
+/// fn withdraw(balance: u32, amount: u32) -> Result<u32> {
+///     if amount > balance {
+///         app_bail!("insufficient_funds", "Balance {} is lower than {}.", balance, amount);
+///     }
+///     Ok(balance - amount)
+/// }
+///
+/// let err = withdraw(10, 20).unwrap_err();
+/// assert!(matches!(err, AppError::Validation(Some("insufficient_funds"), _)));
+/// assert_eq!(err.to_string(), "Balance 10 is lower than 20.");
+/// ```
+#[macro_export]
+macro_rules! app_bail {
+    ($code:expr, $($arg:tt)+) => {
+        return ::core::result::Result::Err(
+            $crate::result::AppError::Validation(::core::option::Option::Some($code), format!($($arg)+)).into(),
+        )
+    };
+}
+
+/// Return early with an [`AppError::Validation`] error if the
+/// condition is not satisfied, with the code and the message
+/// formatted with the arguments passed.
+///
+/// # Example
+/// ```
+/// use actix_contrib_rest::app_ensure;
+/// use actix_contrib_rest::result::{AppError, Result};
+///
+/// fn withdraw(balance: u32, amount: u32) -> Result<u32> {
+///     app_ensure!(amount > 0, "invalid_amount", "The amount must be positive.");
+///     app_ensure!(amount <= balance, "insufficient_funds", "Balance {} is lower than {}.", balance, amount);
+///     Ok(balance - amount)
+/// }
+///
+/// assert_eq!(withdraw(10, 5).unwrap(), 5);
+/// let err = withdraw(10, 0).unwrap_err();
+/// assert!(matches!(err, AppError::Validation(Some("invalid_amount"), _)));
+/// ```
+#[macro_export]
+macro_rules! app_ensure {
+    ($cond:expr, $code:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::app_bail!($code, $($arg)+);
+        }
+    };
+}
+
 /// Type to use as result for a request handlers in order
 /// to allow [`AppError`] to handle properly response
 /// errors.
@@ -1,11 +1,18 @@
 //! Utils to deal with streams data types.
 
-use crate::result::{AppError, Result};
+use crate::page::{has_next_page, Page};
+use crate::result::{send_request_error, AppError, Result};
 
 use actix_http::error::PayloadError;
 use actix_web::web::Bytes;
-use awc::ResponseBody;
+use awc::{ClientRequest, ResponseBody};
+use futures_core::future::LocalBoxFuture;
 use futures_core::stream::Stream;
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
 
 /// Read body from an HTTP response as string.
 /// The content has to be encoded in UTF-8, otherwise
@@ -36,3 +43,110 @@ where
     let bytes = body.await.unwrap().to_vec();
     String::from_utf8(bytes).map_err(|e| AppError::Unexpected(e.into()))
 }
+
+/// Build a stream with the items of all the pages of a paginated API
+/// that returns [`Page`] results, e.g. other services built with this
+/// crate, with the name of the service passed. The pages are requested one at a time with the request built
+/// by the function passed, that receives the offset of the page
+/// to request, until there are no more results.
+///
+/// Any error requesting or deserializing a page is returned as an
+/// [`AppError::Upstream`] error of the service, and the stream ends.
+///
+/// # Example
+/// ```no_run
+/// use actix_contrib_rest::stream::follow_pages;
+/// use awc::Client;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     id: i64,
+///     name: String,
+/// }
+///
+/// fn sync_users() {
+///     let client = Client::default();
+///     let users = follow_pages::<User, _>("users", move |offset| {
+///         client.get(format!("http://users-api/users?offset={offset}&page_size=100"))
+///     });
+///     // ... e.g. users.next().await with `futures::StreamExt`
+/// }
+/// ```
+pub fn follow_pages<T, F>(service: &'static str, request: F) -> PageStream<T>
+where
+    T: DeserializeOwned + 'static,
+    F: Fn(i64) -> ClientRequest + 'static,
+{
+    PageStream {
+        service,
+        request: Box::new(request),
+        items: VecDeque::new(),
+        next_offset: Some(0),
+        fetching: None,
+    }
+}
+
+/// Stream of the items of all the pages of a paginated API,
+/// see [`follow_pages()`].
+pub struct PageStream<T> {
+    service: &'static str,
+    request: Box<dyn Fn(i64) -> ClientRequest>,
+    items: VecDeque<T>,
+    next_offset: Option<i64>,
+    fetching: Option<LocalBoxFuture<'static, Result<Page<T>>>>,
+}
+
+// The items are never pinned
+impl<T> Unpin for PageStream<T> {}
+
+impl<T: DeserializeOwned + 'static> Stream for PageStream<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(item) = this.items.pop_front() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if let Some(fetching) = this.fetching.as_mut() {
+                let page = ready!(fetching.as_mut().poll(cx));
+                this.fetching = None;
+                match page {
+                    Ok(page) => {
+                        this.next_offset = next_offset(&page);
+                        this.items.extend(page.data);
+                    }
+                    Err(e) => {
+                        this.next_offset = None;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                continue;
+            }
+            match this.next_offset.take() {
+                Some(offset) => {
+                    this.fetching = Some(fetch_page(this.service, (this.request)(offset)))
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// The offset of the page after the one passed, if there are more results.
+fn next_offset<T>(page: &Page<T>) -> Option<i64> {
+    let len = page.data.len() as i64;
+    let has_next = len > 0 && has_next_page(page.offset, len, page.page_size, page.total, page.has_next);
+    has_next.then_some(page.offset + len)
+}
+
+fn fetch_page<T: DeserializeOwned + 'static>(
+    service: &'static str,
+    req: ClientRequest,
+) -> LocalBoxFuture<'static, Result<Page<T>>> {
+    Box::pin(async move {
+        let mut res = req.send().await.map_err(|e| send_request_error(service, e))?;
+        Page::from_response(&mut res, service).await
+    })
+}